@@ -25,3 +25,96 @@ window.onload = () => {
 	})
 
 }
+
+document.addEventListener("click", (event) => {
+	const button = event.target.closest("button[data-copy]")
+	if (!button) return
+	navigator.clipboard.writeText(button.dataset.copy).then(() => {
+		const label = button.innerText
+		button.innerText = "copied!"
+		setTimeout(() => button.innerText = label, 1500)
+	})
+})
+
+// keep open named clips in sync with writes from other tabs and machines
+let clipEvents = null;
+function subscribeClip(root = document) {
+	const clip = root.querySelector("[data-events]")
+	if (clipEvents) {
+		clipEvents.close()
+		clipEvents = null
+	}
+	if (!clip) return
+	clipEvents = new EventSource(clip.dataset.events)
+	clipEvents.addEventListener("clip", (event) => {
+		clip.querySelector(".clip-body").innerHTML = event.data
+	})
+}
+
+document.addEventListener("DOMContentLoaded", () => subscribeClip())
+document.addEventListener("htmx:afterSwap", () => subscribeClip())
+
+// anonymous posts need sha256(challenge:nonce) to start with `difficulty` zero bits
+function leadingZeroBits(bytes) {
+	let bits = 0
+	for (const byte of bytes) {
+		if (byte === 0) {
+			bits += 8
+			continue
+		}
+		return bits + Math.clz32(byte) - 24
+	}
+	return bits
+}
+
+async function solveChallenge() {
+	const { challenge, difficulty } = await fetch("/ecb/challenge").then(r => r.json())
+	const encoder = new TextEncoder()
+	for (let nonce = 0; ; nonce++) {
+		const data = encoder.encode(`${challenge}:${nonce}`)
+		const hash = new Uint8Array(await crypto.subtle.digest("SHA-256", data))
+		if (leadingZeroBits(hash) >= difficulty) {
+			return { challenge, nonce: String(nonce) }
+		}
+	}
+}
+
+async function preparePow() {
+	if (!document.querySelector("#pow")) return
+	const { challenge, nonce } = await solveChallenge()
+	document.querySelectorAll("input[name=pow_challenge]").forEach(i => i.value = challenge)
+	document.querySelectorAll("input[name=pow_nonce]").forEach(i => i.value = nonce)
+}
+
+document.addEventListener("DOMContentLoaded", preparePow)
+document.addEventListener("htmx:afterRequest", (event) => {
+	// each challenge is spent by one post
+	if (event.detail.requestConfig.verb === "post") preparePow()
+})
+
+// screenshots pasted into the random clip textarea become image clips,
+// images are always public so the named and private forms ignore them
+async function uploadImage(form, file) {
+	const swap = document.querySelector("#swap")
+	const params = new URLSearchParams()
+	form.querySelectorAll("input[name^=pow_]").forEach(i => params.set(i.name, i.value))
+	const response = await fetch(`/ecb/image?${params}`, {
+		method: "POST",
+		headers: { "Content-Type": file.type },
+		body: file,
+	})
+	swap.innerHTML = await response.text()
+	htmx.process(swap)
+	subscribeClip()
+	preparePow()
+}
+
+document.addEventListener("paste", (event) => {
+	const form = event.target.closest("#write-random form")
+	if (!form || event.target.name !== "content") return
+	const item = Array.from(event.clipboardData.items)
+		.find(item => item.kind === "file" && item.type.startsWith("image/"))
+	if (!item) return
+	event.preventDefault()
+	uploadImage(form, item.getAsFile())
+})
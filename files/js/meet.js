@@ -39,7 +39,7 @@ function El(name, value="", attributes=null) {
 	return el;
 }
 
-function savableNote(card) {
+function savableNote(card, base) {
 	const saveButton = $("button", card);
 	const noteid = card.getAttribute("noteid")
 	const deleteNoteButton = El("button", {class: "edit-delete"}, "🗑")
@@ -50,7 +50,7 @@ function savableNote(card) {
 		if (!confirm("Deletar nota?")) {
 			return;
 		}
-		const r = await (fetch("/meet/user/note/"+noteid, {
+		const r = await (fetch(base+"/"+noteid, {
 			method: "DELETE",
 		}).then(
 			a=>a.status,
@@ -59,7 +59,7 @@ function savableNote(card) {
 		if (r === 200) {
 			card.remove();
 		} else {
-			savableNote(card)
+			savableNote(card, base)
 		}
 	})
 
@@ -68,27 +68,28 @@ function savableNote(card) {
 		const content = editArea.value;
 		const contentArea = El("span", content);
 
-		const r = await (fetch("/meet/user/note/"+noteid, {
+		const [r, note] = await (fetch(base+"/"+noteid, {
 			method: "PUT",
 			headers: {"Content-Type":"application/json"},
 			body: JSON.stringify({content})
 		}).then(
-			a=>a.status,
-			()=>600
+			async a=>[a.status, a.ok ? await a.json() : null],
+			()=>[600, null]
 		))
 
 		if (r === 200) {
 			deleteNoteButton.remove();
 			editArea.remove();
 			card.prepend(contentArea);
-			editableNote(card)
+			setAttribution(card, note);
+			editableNote(card, base)
 		} else {
-			savableNote(card)
+			savableNote(card, base)
 		}
 	}, {once: true})
 }
 
-function editableNote(card) {
+function editableNote(card, base) {
 	const editButton = $("button", card);
 	editButton.innerText="✎"
 	editButton.addEventListener("click", ()=>{
@@ -98,11 +99,20 @@ function editableNote(card) {
 		console.log(content, editArea)
 		$("span", card).remove()
 		card.prepend(editArea)
-		savableNote(card)
+		savableNote(card, base)
 	}, {once: true})
 }
 
-function makeNote({id, content}) {
+// who wrote and last changed a group note, personal notes have neither
+function setAttribution(card, note) {
+	const by = $("small.note-author", card);
+	if (by === null || !note.author_name) return;
+	const edited = note.last_edited_by_name && note.last_edited_by_name !== note.author_name;
+	by.innerText = "by "+note.author_name+(edited ? ", edited by "+note.last_edited_by_name : "");
+}
+
+function makeNote(note, base="/meet/user/note", editable=true) {
+	const {id, content} = note;
 	const [bkcolor, txcolor] = autoColor();
 	const card = El("div", {
 		noteid: id,
@@ -112,20 +122,26 @@ function makeNote({id, content}) {
 			"color": txcolor,
 		},
 	}, [
-		El("button", {class:"edit", type: "button"}),
 		El("span", content),
 	])
-	editableNote(card)
+	if ("author_id" in note) {
+		card.appendChild(El("small", {class: "note-author"}));
+		setAttribution(card, note);
+	}
+	if (editable) {
+		card.prepend(El("button", {class:"edit", type: "button"}));
+		editableNote(card, base)
+	}
 	return card;
 }
 
-function makeNoteCreator(noteBoard) {
+function makeNoteCreator(noteBoard, base="/meet/user/note") {
 	const button = El("button", {type: "button", title: "create"}, "+");
 	const textArea = El("textarea");
 	const card = El("div", [ textArea, button ]);
 	button.addEventListener("click", async ()=>{
 		const content = textArea.value;
-		const r = await fetch("/meet/user/note", {
+		const r = await fetch(base, {
 			method: "POST",
 			headers: {"Content-Type":"application/json"},
 			body: JSON.stringify({content})
@@ -133,7 +149,7 @@ function makeNoteCreator(noteBoard) {
 		if (r.status === 200) {
 			const note = await r.json();
 			textArea.value = ""
-			const noteCard = makeNote(note)
+			const noteCard = makeNote(note, base)
 			noteBoard.appendChild(noteCard)
 		} else {
 			alert("failed to create note")
@@ -155,7 +171,7 @@ function respondToVisibility(element, callback) {
 	newVisilibityObserver(callback).observe(element);
 }
 
-function listTriggerNote(noteBoard) {
+function listTriggerNote(noteBoard, base="/meet/user/note", editable=true) {
 	const loader = El("img", {src: "https://htmx.org/img/bars.svg"});
 	noteBoard.appendChild(loader)
 	let page = 0;
@@ -163,11 +179,11 @@ function listTriggerNote(noteBoard) {
 	respondToVisibility(loader, async ()=>{
 		const query = new URLSearchParams({page, page_size});
 		page++;
-		fetch("/meet/user/note?"+query)
+		fetch(base+"?"+query)
 			.then(a=>a.json())
 			.then(notes=>{
 				notes.content
-					.map(makeNote)
+					.map(n=>makeNote(n, base, editable))
 					.forEach(e=>noteBoard.insertBefore(e, loader))
 				if (notes.taken < page_size) {
 					loader.remove();
@@ -193,7 +209,212 @@ async function initNoteSystem() {
 
 async function initCalendarSystem() {
 }
+async function sendJSON(url, method, body) {
+	const r = await fetch(url, {
+		method,
+		headers: {"Content-Type":"application/json"},
+		body: JSON.stringify(body),
+	}).catch(()=>null);
+	if (r === null) {
+		alert("connection failed");
+		return null;
+	}
+	if (!r.ok) {
+		alert(await r.text());
+		return null;
+	}
+	return r;
+}
+
+function makeInvite(invite, refresh) {
+	const answer = action => async ()=>{
+		const r = await sendJSON("/meet/user/group/invite/"+invite.invite_id, "POST", {action});
+		if (r !== null) refresh();
+	}
+	const accept = El("button", {type: "button"}, "accept");
+	const refuse = El("button", {type: "button"}, "refuse");
+	accept.addEventListener("click", answer("accept"));
+	refuse.addEventListener("click", answer("refuse"));
+	return El("li", {class: "invite"}, [El("span", invite.group_name), accept, refuse]);
+}
+
+const _roles = ["viewer", "editor", "admin", "owner"];
+const outranks = (role, other) => _roles.indexOf(role) > _roles.indexOf(other);
+
+function makeMember(group, member, myRole, refresh) {
+	const label = El("span", {class: "role"}, " ("+member.role+")");
+	const item = El("li", {class: "member"}, [El("span", member.name), label]);
+	if (!outranks(myRole, "editor") || !outranks(myRole, member.role)) {
+		return item;
+	}
+	const role = El("select", {title: "role"},
+		_roles.filter(r=>outranks(myRole, r)).map(r=>El("option", {value: r}, r)));
+	role.value = member.role;
+	role.addEventListener("change", async ()=>{
+		const r = await sendJSON("/meet/group/"+group.id+"/role/"+member.id, "PUT", {role: role.value});
+		if (r === null) {
+			role.value = member.role;
+			return;
+		}
+		member.role = role.value;
+		label.innerText = " ("+member.role+")";
+	})
+	const remove = El("button", {type: "button", title: "remove"}, "✕");
+	remove.addEventListener("click", async ()=>{
+		if (!confirm("Remove "+member.name+"?")) return;
+		const r = await fetch("/meet/user/group/"+group.id+"/user/"+member.id, {method: "DELETE"})
+			.catch(()=>({ok: false}));
+		if (r.ok) item.remove(); else alert("failed to remove member");
+	})
+	item.append(role, remove);
+	if (myRole === "owner") {
+		const promote = El("button", {type: "button", title: "transfer ownership"}, "👑");
+		promote.addEventListener("click", async ()=>{
+			if (!confirm("Transfer ownership to "+member.name+"?")) return;
+			const r = await sendJSON("/meet/user/group/"+group.id, "PATCH", {owner_id: member.id});
+			if (r !== null) refresh();
+		})
+		item.appendChild(promote);
+	}
+	return item;
+}
+
+// the group's sticky notes, loaded the first time the group is opened,
+// members below editor only read them
+function makeGroupNotes(group) {
+	const base = "/meet/group/"+group.id+"/note";
+	const container = El("div", {class: "note-container"});
+	const creator = makeNoteCreator(container, base);
+	const board = El("div", {class: "group-notes"}, [container]);
+	return [board, myRole=>{
+		const writes = !outranks("editor", myRole);
+		if (writes) board.prepend(creator); else creator.remove();
+		if (container.childElementCount === 0) listTriggerNote(container, base, writes);
+	}];
+}
+
+function makeInviteLink(group, link) {
+	const url = El("input", {type: "text", readonly: "", value: link.url});
+	const uses = link.max_uses === null ? link.uses : link.uses+"/"+link.max_uses;
+	const expires = link.expires_at === null ? "" : ", until "+new Date(link.expires_at).toLocaleString();
+	const revoke = El("button", {type: "button", title: "revoke"}, "✕");
+	const item = El("li", {class: "invite-link"}, [url, El("small", " used "+uses+expires), revoke]);
+	url.addEventListener("focus", ()=>url.select());
+	revoke.addEventListener("click", async ()=>{
+		if (!confirm("Revoke this link?")) return;
+		const r = await fetch("/meet/group/"+group.id+"/invite-link/"+link.token, {method: "DELETE"})
+			.catch(()=>({ok: false}));
+		if (r.ok) item.remove(); else alert("failed to revoke link");
+	})
+	return item;
+}
+
+// shareable invite links, loaded with the members for admins and the owner
+function makeInviteLinks(group) {
+	const links = El("ul", {class: "invite-links"});
+	const expires = El("input", {type: "datetime-local", title: "expires"});
+	const maxUses = El("input", {type: "number", min: "1", placeholder: "max uses"});
+	const create = El("button", {type: "button"}, "create link");
+	create.addEventListener("click", async ()=>{
+		const r = await sendJSON("/meet/group/"+group.id+"/invite-link", "POST", {
+			expires_at: expires.value ? new Date(expires.value).toISOString() : null,
+			max_uses: maxUses.value ? Number(maxUses.value) : null,
+		});
+		if (r !== null) {
+			expires.value = maxUses.value = "";
+			links.appendChild(makeInviteLink(group, await r.json()));
+		}
+	})
+	const load = async ()=>{
+		const active = await fetch("/meet/group/"+group.id+"/invite-link")
+			.then(a=>a.ok ? a.json() : [], ()=>[]);
+		links.replaceChildren(...active.map(l=>makeInviteLink(group, l)));
+	}
+	return [El("div", {class: "group-links"}, [expires, maxUses, create, links]), load];
+}
+
+function makeGroup(group, me, refresh) {
+	const members = El("ul", {class: "members"});
+	const controls = El("div", {class: "group-controls"});
+	const [notes, loadNotes] = makeGroupNotes(group);
+	const [links, loadLinks] = makeInviteLinks(group);
+	const card = El("details", {class: "group", groupid: group.id}, [
+		El("summary", group.name),
+		El("p", group.description ?? ""),
+		members,
+		controls,
+		notes,
+	]);
+
+	const name = El("input", {type: "text", placeholder: "username"});
+	const invite = El("button", {type: "button"}, "invite");
+	invite.addEventListener("click", async ()=>{
+		const r = await sendJSON("/meet/user/group/"+group.id+"/invite", "POST", {name: name.value});
+		if (r !== null) {
+			name.value = "";
+			alert("invite sent");
+		}
+	})
+	const remove = El("button", {type: "button", class: "group-delete"}, "delete group");
+	remove.addEventListener("click", async ()=>{
+		if (!confirm("Delete "+group.name+"?")) return;
+		const r = await fetch("/meet/user/group/"+group.id, {method: "DELETE"})
+			.catch(()=>({ok: false}));
+		if (r.ok) card.remove(); else alert("failed to delete group");
+	})
+
+	card.addEventListener("toggle", async ()=>{
+		if (!card.open) return;
+		const users = await fetch("/meet/user/group/"+group.id+"/users")
+			.then(a=>a.json(), ()=>[]);
+		const myRole = users.find(u=>u.id === me.id)?.role ?? "viewer";
+		loadNotes(myRole);
+		members.replaceChildren(...users.map(u=>makeMember(group, u, myRole, refresh)));
+		controls.replaceChildren();
+		if (outranks(myRole, "editor")) {
+			controls.append(El("div", {class: "group-invite"}, [name, invite]), links);
+			loadLinks();
+		}
+		if (myRole === "owner") {
+			controls.appendChild(remove);
+		}
+	})
+	return card;
+}
+
+function makeGroupCreator(refresh) {
+	const name = El("input", {type: "text", placeholder: "group name"});
+	const description = El("input", {type: "text", placeholder: "description"});
+	const button = El("button", {type: "button", title: "create"}, "+");
+	button.addEventListener("click", async ()=>{
+		const r = await sendJSON("/meet/user/group", "POST", {
+			name: name.value,
+			description: description.value || null,
+		});
+		if (r !== null) {
+			name.value = description.value = "";
+			refresh();
+		}
+	})
+	return El("div", {class: "group-creator"}, [name, description, button]);
+}
+
 async function initGroupSystem() {
+	const groupBoard = $("#groups");
+	const me = await fetch("/meet/user/self").then(a=>a.ok ? a.json() : null, ()=>null);
+	if (me === null) return;
+	const invites = El("ul", {class: "invites"});
+	const groups = El("div", {class: "group-list"});
+	const refresh = async ()=>{
+		const [pending, mine] = await Promise.all([
+			fetch("/meet/user/group/invites").then(a=>a.json(), ()=>[]),
+			fetch("/meet/user/groups").then(a=>a.json(), ()=>[]),
+		]);
+		invites.replaceChildren(...pending.map(i=>makeInvite(i, refresh)));
+		groups.replaceChildren(...mine.map(g=>makeGroup(g, me, refresh)));
+	}
+	groupBoard.replaceChildren(makeGroupCreator(refresh), invites, groups);
+	await refresh();
 }
 
 window.onload = async () => {
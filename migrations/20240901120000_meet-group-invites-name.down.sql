@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE meet.group_invites RENAME TO group_nvites;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE meet.group_nvites RENAME TO group_invites;
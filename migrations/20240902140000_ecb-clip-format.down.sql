@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE ecb.random DROP COLUMN format, DROP COLUMN language;
+ALTER TABLE ecb.named DROP COLUMN format, DROP COLUMN language;
+ALTER TABLE ecb.private DROP COLUMN format, DROP COLUMN language;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+ALTER TABLE ecb.random
+	ADD COLUMN format TEXT NOT NULL DEFAULT 'plain' CHECK (format IN ('plain', 'markdown', 'code')),
+	ADD COLUMN language TEXT;
+ALTER TABLE ecb.named
+	ADD COLUMN format TEXT NOT NULL DEFAULT 'plain' CHECK (format IN ('plain', 'markdown', 'code')),
+	ADD COLUMN language TEXT;
+ALTER TABLE ecb.private
+	ADD COLUMN format TEXT NOT NULL DEFAULT 'plain' CHECK (format IN ('plain', 'markdown', 'code')),
+	ADD COLUMN language TEXT;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE ecb.random DROP COLUMN created_at, DROP COLUMN updated_at;
+ALTER TABLE ecb.named DROP COLUMN created_at, DROP COLUMN updated_at;
+ALTER TABLE ecb.private DROP COLUMN created_at, DROP COLUMN updated_at;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX ecb.ecb_named_prefix_idx;
+DROP INDEX ecb.ecb_named_search_idx;
+ALTER TABLE ecb.named DROP COLUMN search, DROP COLUMN searchable;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE ecb.named
+	ADD COLUMN searchable BOOLEAN NOT NULL DEFAULT TRUE,
+	ADD COLUMN search TSVECTOR NOT NULL GENERATED ALWAYS AS (
+		setweight(to_tsvector('simple', name), 'A') ||
+		setweight(to_tsvector('english', content), 'B')
+	) STORED;
+CREATE INDEX ecb_named_search_idx ON ecb.named USING GIN (search);
+CREATE INDEX ecb_named_prefix_idx ON ecb.named (name text_pattern_ops);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX ecb.ecb_creations_ip_idx;
+DROP TABLE ecb.creations;
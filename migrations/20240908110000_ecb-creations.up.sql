@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE ecb.creations (
+	id SERIAL NOT NULL PRIMARY KEY,
+	ip TEXT NOT NULL,
+	challenge UUID UNIQUE,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX ecb_creations_ip_idx ON ecb.creations (ip, created_at);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP FUNCTION ecb.private_access;
+DROP TABLE ecb.private_grants;
+ALTER TABLE ecb.private DROP COLUMN shared_key;
@@ -0,0 +1,37 @@
+-- Add up migration script here
+-- clip password encrypted with the server key, set once the owner shares the clip
+ALTER TABLE ecb.private ADD COLUMN shared_key BYTEA;
+
+CREATE TABLE ecb.private_grants (
+	id SERIAL NOT NULL PRIMARY KEY,
+	clip_name TEXT NOT NULL REFERENCES ecb.private(name) ON DELETE CASCADE ON UPDATE CASCADE,
+	account_id UUID REFERENCES inter.accounts(id) ON DELETE CASCADE,
+	group_id UUID REFERENCES meet.groups(id) ON DELETE CASCADE,
+	access TEXT NOT NULL CHECK (access IN ('read', 'write')),
+	CHECK ((account_id IS NULL) <> (group_id IS NULL)),
+	UNIQUE (clip_name, account_id),
+	UNIQUE (clip_name, group_id)
+);
+CREATE INDEX ecb_private_grants_clip_idx ON ecb.private_grants (clip_name);
+
+-- 'write', 'read' or NULL: what an account may do with a private clip
+-- without knowing its password, either as owner or through a grant
+CREATE FUNCTION ecb.private_access(clip TEXT, account UUID) RETURNS TEXT AS $$
+	SELECT CASE
+		WHEN p.owner_id = account THEN 'write'
+		ELSE (
+			SELECT CASE
+				WHEN bool_or(g.access = 'write') THEN 'write'
+				WHEN COUNT(*) > 0 THEN 'read'
+			END
+			FROM ecb.private_grants as g
+			WHERE g.clip_name = clip AND (
+				g.account_id = account OR g.group_id IN (
+					SELECT group_id FROM meet.group_users WHERE user_id = account
+				)
+			)
+		)
+	END
+	FROM ecb.private as p
+	WHERE p.name = clip
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DELETE FROM ecb.named WHERE namespace_id IS NOT NULL;
+DROP INDEX ecb.ecb_named_namespace_idx;
+DROP INDEX ecb.ecb_named_idx;
+CREATE UNIQUE INDEX ecb_named_idx ON ecb.named (name);
+ALTER TABLE ecb.named ADD CONSTRAINT named_name_key UNIQUE (name);
+ALTER TABLE ecb.named DROP COLUMN namespace_id;
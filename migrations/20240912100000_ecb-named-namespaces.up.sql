@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- named clips either live in the global namespace (NULL) or in an account's
+ALTER TABLE ecb.named
+	ADD COLUMN namespace_id UUID REFERENCES inter.accounts(id) ON DELETE CASCADE;
+ALTER TABLE ecb.named DROP CONSTRAINT named_name_key;
+DROP INDEX ecb.ecb_named_idx;
+CREATE UNIQUE INDEX ecb_named_idx ON ecb.named (name) WHERE namespace_id IS NULL;
+CREATE UNIQUE INDEX ecb_named_namespace_idx ON ecb.named (namespace_id, name) WHERE namespace_id IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE ecb.access_log;
+ALTER TABLE ecb.random DROP COLUMN views, DROP COLUMN last_accessed;
+ALTER TABLE ecb.named DROP COLUMN views, DROP COLUMN last_accessed;
+ALTER TABLE ecb.private DROP COLUMN views, DROP COLUMN last_accessed;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+ALTER TABLE ecb.random
+	ADD COLUMN views BIGINT NOT NULL DEFAULT 0,
+	ADD COLUMN last_accessed TIMESTAMPTZ;
+ALTER TABLE ecb.named
+	ADD COLUMN views BIGINT NOT NULL DEFAULT 0,
+	ADD COLUMN last_accessed TIMESTAMPTZ;
+ALTER TABLE ecb.private
+	ADD COLUMN views BIGINT NOT NULL DEFAULT 0,
+	ADD COLUMN last_accessed TIMESTAMPTZ;
+
+-- clip is the random code, the (account/)name of a named clip or the private name
+CREATE TABLE ecb.access_log (
+	id BIGSERIAL NOT NULL PRIMARY KEY,
+	kind TEXT NOT NULL CHECK (kind IN ('random', 'named', 'private')),
+	clip TEXT NOT NULL,
+	ip TEXT NOT NULL,
+	success BOOLEAN NOT NULL,
+	at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX ecb_access_log_clip_idx ON ecb.access_log (kind, clip, id);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE inter.accounts DROP COLUMN admin;
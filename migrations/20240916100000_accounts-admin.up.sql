@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE inter.accounts ADD COLUMN admin BOOLEAN NOT NULL DEFAULT false;
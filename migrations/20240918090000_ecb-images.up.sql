@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE ecb.images (
+	id SERIAL PRIMARY KEY,
+	mime TEXT NOT NULL,
+	width INTEGER NOT NULL,
+	height INTEGER NOT NULL,
+	data BYTEA NOT NULL,
+	-- always png
+	thumbnail BYTEA NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE ecb.blocklist;
+DROP TABLE ecb.reports;
+ALTER TABLE ecb.named DROP COLUMN hidden;
+ALTER TABLE ecb.random DROP COLUMN hidden;
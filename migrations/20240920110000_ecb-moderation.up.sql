@@ -0,0 +1,22 @@
+-- Add up migration script here
+ALTER TABLE ecb.random ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE ecb.named ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE ecb.reports (
+	id SERIAL PRIMARY KEY,
+	kind TEXT NOT NULL CHECK (kind IN ('random', 'named')),
+	clip TEXT NOT NULL,
+	reason TEXT NOT NULL,
+	ip TEXT NOT NULL,
+	reporter_id UUID REFERENCES inter.accounts(id) ON DELETE SET NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+	resolved BOOLEAN NOT NULL DEFAULT false
+);
+-- one open report per clip and address
+CREATE UNIQUE INDEX ecb_reports_open_idx ON ecb.reports (kind, clip, ip) WHERE NOT resolved;
+
+-- sha256 of blocked clip contents
+CREATE TABLE ecb.blocklist (
+	hash BYTEA PRIMARY KEY,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
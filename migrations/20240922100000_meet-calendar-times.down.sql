@@ -0,0 +1,22 @@
+-- Add down migration script here
+ALTER TABLE meet.group_calendar_entries ADD COLUMN time DATE;
+UPDATE meet.group_calendar_entries SET time = (starts_at AT TIME ZONE time_zone)::date;
+ALTER TABLE meet.group_calendar_entries
+	ALTER COLUMN time SET NOT NULL,
+	DROP CONSTRAINT group_calendar_entries_span,
+	DROP COLUMN starts_at,
+	DROP COLUMN ends_at,
+	DROP COLUMN all_day,
+	DROP COLUMN time_zone;
+
+ALTER TABLE meet.user_calendar_entries ADD COLUMN time DATE;
+UPDATE meet.user_calendar_entries SET time = (starts_at AT TIME ZONE time_zone)::date;
+ALTER TABLE meet.user_calendar_entries
+	ALTER COLUMN time SET NOT NULL,
+	DROP CONSTRAINT user_calendar_entries_span,
+	DROP COLUMN starts_at,
+	DROP COLUMN ends_at,
+	DROP COLUMN all_day,
+	DROP COLUMN time_zone;
+
+ALTER TABLE inter.accounts DROP COLUMN time_zone;
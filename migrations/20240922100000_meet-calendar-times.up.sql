@@ -0,0 +1,33 @@
+-- Add up migration script here
+ALTER TABLE inter.accounts ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
+
+-- existing dates become all-day entries in UTC
+ALTER TABLE meet.user_calendar_entries
+	ADD COLUMN starts_at TIMESTAMPTZ,
+	ADD COLUMN ends_at TIMESTAMPTZ,
+	ADD COLUMN all_day BOOLEAN NOT NULL DEFAULT false,
+	ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
+UPDATE meet.user_calendar_entries SET
+	starts_at = time::timestamp AT TIME ZONE 'UTC',
+	ends_at = (time + 1)::timestamp AT TIME ZONE 'UTC',
+	all_day = true;
+ALTER TABLE meet.user_calendar_entries
+	ALTER COLUMN starts_at SET NOT NULL,
+	ALTER COLUMN ends_at SET NOT NULL,
+	ADD CONSTRAINT user_calendar_entries_span CHECK (ends_at > starts_at),
+	DROP COLUMN time;
+
+ALTER TABLE meet.group_calendar_entries
+	ADD COLUMN starts_at TIMESTAMPTZ,
+	ADD COLUMN ends_at TIMESTAMPTZ,
+	ADD COLUMN all_day BOOLEAN NOT NULL DEFAULT false,
+	ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
+UPDATE meet.group_calendar_entries SET
+	starts_at = time::timestamp AT TIME ZONE 'UTC',
+	ends_at = (time + 1)::timestamp AT TIME ZONE 'UTC',
+	all_day = true;
+ALTER TABLE meet.group_calendar_entries
+	ALTER COLUMN starts_at SET NOT NULL,
+	ALTER COLUMN ends_at SET NOT NULL,
+	ADD CONSTRAINT group_calendar_entries_span CHECK (ends_at > starts_at),
+	DROP COLUMN time;
@@ -0,0 +1,12 @@
+-- Add down migration script here
+ALTER TABLE meet.group_calendar_entries
+	DROP COLUMN recurrence_at,
+	DROP COLUMN parent_id,
+	DROP COLUMN exdates,
+	DROP COLUMN rrule;
+
+ALTER TABLE meet.user_calendar_entries
+	DROP COLUMN recurrence_at,
+	DROP COLUMN parent_id,
+	DROP COLUMN exdates,
+	DROP COLUMN rrule;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- rrule is an RFC 5545 RRULE value without the "RRULE:" prefix, expanded
+-- from starts_at in time_zone. Occurrences edited on their own become
+-- entries pointing at their series, which lists them in exdates.
+ALTER TABLE meet.user_calendar_entries
+	ADD COLUMN rrule TEXT,
+	ADD COLUMN exdates TIMESTAMPTZ[] NOT NULL DEFAULT '{}',
+	ADD COLUMN parent_id INTEGER REFERENCES meet.user_calendar_entries(id) ON DELETE CASCADE,
+	ADD COLUMN recurrence_at TIMESTAMPTZ;
+
+ALTER TABLE meet.group_calendar_entries
+	ADD COLUMN rrule TEXT,
+	ADD COLUMN exdates TIMESTAMPTZ[] NOT NULL DEFAULT '{}',
+	ADD COLUMN parent_id INTEGER REFERENCES meet.group_calendar_entries(id) ON DELETE CASCADE,
+	ADD COLUMN recurrence_at TIMESTAMPTZ;
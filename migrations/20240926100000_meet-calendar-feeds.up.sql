@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- secret tokens of the iCalendar subscription feeds, one per account or group
+CREATE TABLE meet.calendar_feeds (
+	token TEXT NOT NULL PRIMARY KEY,
+	account_id UUID UNIQUE REFERENCES inter.accounts(id) ON DELETE CASCADE,
+	group_id UUID UNIQUE REFERENCES meet.groups(id) ON DELETE CASCADE,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+	CHECK ((account_id IS NULL) <> (group_id IS NULL))
+);
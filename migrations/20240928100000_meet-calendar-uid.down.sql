@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX meet.group_calendar_entries_uid;
+ALTER TABLE meet.group_calendar_entries DROP COLUMN uid;
+
+DROP INDEX meet.user_calendar_entries_uid;
+ALTER TABLE meet.user_calendar_entries DROP COLUMN uid;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- UID of entries imported from iCalendar files, entries created here are
+-- exported as "<user|group>-<id>@<domain>" instead
+ALTER TABLE meet.user_calendar_entries ADD COLUMN uid TEXT;
+CREATE UNIQUE INDEX user_calendar_entries_uid ON meet.user_calendar_entries (owner_id, uid);
+
+ALTER TABLE meet.group_calendar_entries ADD COLUMN uid TEXT;
+CREATE UNIQUE INDEX group_calendar_entries_uid ON meet.group_calendar_entries (group_id, uid);
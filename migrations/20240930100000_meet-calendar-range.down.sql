@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX meet.group_calendar_entries_range;
+CREATE INDEX notebook_group_calendar_entries ON meet.group_calendar_entries(group_id);
+
+DROP INDEX meet.user_calendar_entries_range;
+CREATE INDEX notebook_user_calendar_entries ON meet.user_calendar_entries(owner_id);
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- calendars are read by time range, the owner-only indexes are covered by these
+DROP INDEX meet.notebook_user_calendar_entries;
+CREATE INDEX user_calendar_entries_range ON meet.user_calendar_entries (owner_id, starts_at);
+
+DROP INDEX meet.notebook_group_calendar_entries;
+CREATE INDEX group_calendar_entries_range ON meet.group_calendar_entries (group_id, starts_at);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE meet.group_users DROP COLUMN editor;
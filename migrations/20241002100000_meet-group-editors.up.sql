@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- members allowed to write to the group calendar besides the owner
+ALTER TABLE meet.group_users ADD COLUMN editor BOOLEAN NOT NULL DEFAULT false;
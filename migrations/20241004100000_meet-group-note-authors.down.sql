@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE meet.group_notes
+	DROP COLUMN last_edited_by,
+	DROP COLUMN author_id;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- members who wrote and last changed each note, kept when they leave
+ALTER TABLE meet.group_notes
+	ADD COLUMN author_id UUID REFERENCES inter.accounts(id) ON DELETE SET NULL,
+	ADD COLUMN last_edited_by UUID REFERENCES inter.accounts(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE meet.group_users ADD COLUMN editor BOOLEAN NOT NULL DEFAULT false;
+UPDATE meet.group_users SET editor=true WHERE role IN ('admin', 'editor');
+ALTER TABLE meet.group_users DROP COLUMN role;
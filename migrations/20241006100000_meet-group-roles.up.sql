@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- viewers read, editors also write calendar and notes, admins also invite
+-- and remove members, the owner (meet.groups.owner_id) may do anything
+ALTER TABLE meet.group_users ADD COLUMN role TEXT NOT NULL DEFAULT 'viewer'
+	CHECK (role IN ('owner', 'admin', 'editor', 'viewer'));
+UPDATE meet.group_users SET role='editor' WHERE editor;
+UPDATE meet.group_users as u SET role='owner'
+	FROM meet.groups as g
+	WHERE g.id=u.group_id AND g.owner_id=u.user_id;
+ALTER TABLE meet.group_users DROP COLUMN editor;
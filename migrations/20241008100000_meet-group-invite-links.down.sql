@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX meet.group_invite_links_group;
+DROP TABLE meet.group_invite_links;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- shareable links that let whoever opens them join a group
+CREATE TABLE meet.group_invite_links (
+	token TEXT NOT NULL PRIMARY KEY,
+	group_id UUID NOT NULL REFERENCES meet.groups(id) ON DELETE CASCADE,
+	created_by UUID REFERENCES inter.accounts(id) ON DELETE SET NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+	-- never expires or runs out when NULL
+	expires_at TIMESTAMPTZ,
+	max_uses INTEGER CHECK (max_uses > 0),
+	uses INTEGER NOT NULL DEFAULT 0,
+	revoked BOOLEAN NOT NULL DEFAULT false
+);
+CREATE INDEX group_invite_links_group ON meet.group_invite_links (group_id);
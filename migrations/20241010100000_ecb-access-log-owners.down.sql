@@ -0,0 +1,19 @@
+-- Add down migration script here
+DROP INDEX ecb.ecb_access_log_private_idx;
+DROP INDEX ecb.ecb_access_log_named_idx;
+ALTER TABLE ecb.access_log
+	ADD COLUMN kind TEXT CHECK (kind IN ('random', 'named', 'private')),
+	ADD COLUMN clip TEXT;
+
+UPDATE ecb.access_log as l SET kind='named', clip=a.name || '/' || n.name
+	FROM ecb.named as n
+	INNER JOIN inter.accounts as a ON a.id=n.namespace_id
+	WHERE l.named_id=n.id;
+UPDATE ecb.access_log SET kind='private', clip=private_name WHERE private_name IS NOT NULL;
+
+ALTER TABLE ecb.access_log
+	DROP COLUMN named_id,
+	DROP COLUMN private_name,
+	ALTER COLUMN kind SET NOT NULL,
+	ALTER COLUMN clip SET NOT NULL;
+CREATE INDEX ecb_access_log_clip_idx ON ecb.access_log (kind, clip, id);
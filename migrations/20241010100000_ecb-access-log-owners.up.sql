@@ -0,0 +1,23 @@
+-- Add up migration script here
+-- only clips with an owner keep an access log, and it goes with the clip
+ALTER TABLE ecb.access_log
+	ADD COLUMN named_id INTEGER REFERENCES ecb.named(id) ON DELETE CASCADE,
+	ADD COLUMN private_name TEXT REFERENCES ecb.private(name) ON DELETE CASCADE ON UPDATE CASCADE;
+
+UPDATE ecb.access_log as l SET named_id=n.id
+	FROM ecb.named as n
+	INNER JOIN inter.accounts as a ON a.id=n.namespace_id
+	WHERE l.kind='named' AND l.clip=a.name || '/' || n.name;
+UPDATE ecb.access_log as l SET private_name=p.name
+	FROM ecb.private as p
+	WHERE l.kind='private' AND l.clip=p.name AND p.owner_id IS NOT NULL;
+-- random and global named clips, and clips deleted since
+DELETE FROM ecb.access_log WHERE named_id IS NULL AND private_name IS NULL;
+
+DROP INDEX ecb.ecb_access_log_clip_idx;
+ALTER TABLE ecb.access_log
+	DROP COLUMN kind,
+	DROP COLUMN clip,
+	ADD CHECK ((named_id IS NULL) <> (private_name IS NULL));
+CREATE INDEX ecb_access_log_named_idx ON ecb.access_log (named_id, id);
+CREATE INDEX ecb_access_log_private_idx ON ecb.access_log (private_name, id);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DELETE FROM ecb.reports WHERE kind='image';
+ALTER TABLE ecb.reports DROP CONSTRAINT reports_kind_check;
+ALTER TABLE ecb.reports ADD CONSTRAINT reports_kind_check
+	CHECK (kind IN ('random', 'named'));
+
+ALTER TABLE ecb.images DROP COLUMN hidden;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE ecb.images ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE ecb.reports DROP CONSTRAINT reports_kind_check;
+ALTER TABLE ecb.reports ADD CONSTRAINT reports_kind_check
+	CHECK (kind IN ('random', 'named', 'image'));
@@ -7,7 +7,7 @@ use axum::{
     Router,
 };
 use maud::html;
-use tower_cookies::{Cookie, Cookies};
+use tower_cookies::{cookie::SameSite, Cookie, Cookies};
 
 pub fn service() -> Router<PgPool> {
     Router::new()
@@ -86,6 +86,8 @@ RETURNING id"#,
             .path("/")
             .secure(false)
             .http_only(true)
+            // keeps cross-site form posts from carrying the login
+            .same_site(SameSite::Lax)
             .into(),
     );
     Ok(Redirect::to("/"))
@@ -183,6 +185,21 @@ id
     Ok(Account{name, id})
 }
 
+/// Whether the logged in account is an instance admin
+pub async fn is_admin(
+    cookies: &Cookies,
+    pool: &PgPool,
+) -> Result<bool, Error> {
+    let id = get_id(cookies)?;
+    Ok(sqlx::query!(
+        r#"
+SELECT (admin) FROM inter.accounts
+WHERE (id=$1)"#,
+id
+    )
+    .fetch_one(pool).await?.admin)
+}
+
 pub async fn get_nav(
     url: &str,
     cookies: &Cookies,
@@ -227,6 +244,7 @@ WHERE (password=$1 AND name=$2)"#,
             .path("/")
             .secure(false)
             .http_only(true)
+            .same_site(SameSite::Lax)
             .into(),
     );
     Ok(Redirect::to("/"))
@@ -21,3 +21,51 @@ where
     symm::decrypt(cipher, &key_hash, None, data.as_ref())
 }
 
+
+const SERVER_KEY: &str = include_str!("../.priv.pem");
+// .priv.pem also signs the JWTs, sealing uses a key derived under its own label
+const SEAL_LABEL: &[u8] = b"sr-rs seal v1\0";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+fn seal_key() -> [u8; 32] {
+    let mut hasher = openssl::sha::Sha256::new();
+    hasher.update(SEAL_LABEL);
+    hasher.update(SERVER_KEY.as_bytes());
+    hasher.finish()
+}
+
+/// Encrypts with this instance's own key, for secrets the server
+/// has to recover without the user's help. AES-GCM with a random nonce,
+/// stored as nonce, tag and ciphertext
+pub fn seal<P: AsRef<[u8]>>(data: P) -> Result<Vec<u8>, Error> {
+    let mut nonce = [0; NONCE_LEN];
+    openssl::rand::rand_bytes(&mut nonce)?;
+    let mut tag = [0; TAG_LEN];
+    let sealed = symm::encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &seal_key(),
+        Some(&nonce),
+        &[],
+        data.as_ref(),
+        &mut tag,
+    )?;
+    Ok([&nonce[..], &tag, &sealed].concat())
+}
+
+pub fn unseal<P: AsRef<[u8]>>(data: P) -> Result<Vec<u8>, Error> {
+    let data = data.as_ref();
+    if data.len() < NONCE_LEN + TAG_LEN {
+        // nothing openssl reported, but it can't have come from `seal`
+        return Err(Error::get());
+    }
+    let (nonce, rest) = data.split_at(NONCE_LEN);
+    let (tag, sealed) = rest.split_at(TAG_LEN);
+    symm::decrypt_aead(Cipher::aes_256_gcm(), &seal_key(), Some(nonce), &[], sealed, tag)
+}
+
+/// Opens what `seal` produced before it had a key and nonce of its own,
+/// only to seal it again
+pub fn unseal_legacy<P: AsRef<[u8]>>(data: P) -> Result<Vec<u8>, Error> {
+    decrypt(data, SERVER_KEY)
+}
@@ -0,0 +1,267 @@
+//! Line diffs between any two clips.
+//!
+//! Clips are referred to as `r/<code>`, `n/<name>` or `p/<name>`, or by
+//! their permalink. Posting to `/ecb/diff/raw` gives a plain unified diff:
+//!
+//! `curl -d left=r/12 -d right=p/conf -d right_password=... host/ecb/diff/raw`
+use super::*;
+use axum::{http::header, response::Response};
+use limits::env_or;
+use similar::{ChangeTag, DiffOp, InlineChange, TextDiff};
+use std::{sync::LazyLock, time::Duration};
+
+const DEFAULT_CONTEXT: usize = 3;
+// past this the diff falls back to coarser, but still correct, output
+const DIFF_TIMEOUT: Duration = Duration::from_secs(1);
+// the timeout only bounds the diff itself, not the inline changes and rendering
+static MAX_BYTES: LazyLock<usize> = LazyLock::new(|| env_or("ECB_DIFF_BYTES", 256 * 1024));
+static MAX_LINES: LazyLock<usize> = LazyLock::new(|| env_or("ECB_DIFF_LINES", 10_000));
+
+#[derive(serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    #[default]
+    Unified,
+    Split,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DiffForm {
+    left: String,
+    #[serde(default)]
+    left_password: String,
+    right: String,
+    #[serde(default)]
+    right_password: String,
+    #[serde(default)]
+    view: View,
+    context: Option<usize>,
+}
+
+/// Accepts `r/12`, `n/ns/name`, `p/name` and permalinks like `https://host/ecb/n/ns%2Fname`
+async fn resolve(
+    pool: &PgPool,
+    reference: &str,
+    password: &str,
+    cookies: &Cookies,
+    addr: SocketAddr,
+) -> Result<Clip, Error> {
+    let reference = reference.trim();
+    let path = match reference.find("/ecb/") {
+        Some(i) => &reference[i + "/ecb/".len()..],
+        None => reference,
+    };
+    let invalid = || InvalidClipRef(reference.to_owned());
+    let (kind, id) = path.split_once('/').ok_or_else(invalid)?;
+    let id = urlencoding::decode(id).or(Err(invalid()))?;
+    match kind {
+        "r" => get_random(pool, id.parse().or(Err(invalid()))?).await,
+        "n" => get_named(pool, &id).await,
+        "p" => unlock_private(pool, &id, password, cookies, addr).await,
+        _ => Err(invalid()),
+    }
+}
+
+struct Sides {
+    left: Clip,
+    right: Clip,
+    context: usize,
+}
+
+async fn sides(
+    pool: &PgPool,
+    cookies: &Cookies,
+    addr: SocketAddr,
+    form: &DiffForm,
+) -> Result<Sides, Error> {
+    let sides = Sides {
+        left: resolve(pool, &form.left, &form.left_password, cookies, addr).await?,
+        right: resolve(pool, &form.right, &form.right_password, cookies, addr).await?,
+        context: form.context.unwrap_or(DEFAULT_CONTEXT),
+    };
+    let (max_bytes, max_lines) = (*MAX_BYTES, *MAX_LINES);
+    for clip in [&sides.left, &sides.right] {
+        if clip.content.len() > max_bytes || clip.content.lines().count() > max_lines {
+            return Err(TooLargeToDiff(max_bytes, max_lines));
+        }
+    }
+    Ok(sides)
+}
+
+fn text_diff<'a>(left: &'a str, right: &'a str) -> TextDiff<'a, 'a, 'a, str> {
+    TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(left, right)
+}
+
+fn tag_class(tag: ChangeTag) -> &'static str {
+    match tag {
+        ChangeTag::Equal => "equal",
+        ChangeTag::Delete => "delete",
+        ChangeTag::Insert => "insert",
+    }
+}
+
+// the changed words inside a changed line are wrapped in <mark>
+fn line(change: &InlineChange<str>) -> Markup {
+    html! {
+        @for (emphasized, value) in change.iter_strings_lossy() {
+            @let value = value.trim_end_matches(['\n', '\r']);
+            @if emphasized {
+                mark {(value)}
+            } @else {
+                (value)
+            }
+        }
+    }
+}
+
+fn line_no(index: Option<usize>) -> String {
+    index.map(|i| (i + 1).to_string()).unwrap_or_default()
+}
+
+fn hunk_header(ops: &[DiffOp]) -> String {
+    let (first, last) = (&ops[0], &ops[ops.len() - 1]);
+    let old = first.old_range().start..last.old_range().end;
+    let new = first.new_range().start..last.new_range().end;
+    format!("@@ -{},{} +{},{} @@", old.start + 1, old.len(), new.start + 1, new.len())
+}
+
+fn unified<'a>(diff: &'a TextDiff<'a, 'a, 'a, str>, context: usize) -> Markup {
+    html! {
+        table.diff.unified {
+            @for group in diff.grouped_ops(context) {
+                tr.hunk { td colspan="3" {(hunk_header(&group))} }
+                @for op in &group {
+                    @for change in diff.iter_inline_changes(op) {
+                        tr class=(tag_class(change.tag())) {
+                            td.line-no {(line_no(change.old_index()))}
+                            td.line-no {(line_no(change.new_index()))}
+                            td.line { pre {(line(&change))} }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// changed lines are paired up row by row, the shorter side padded with blanks
+fn split<'a>(diff: &'a TextDiff<'a, 'a, 'a, str>, context: usize) -> Markup {
+    let cell = |change: Option<&InlineChange<str>>, old: bool| {
+        html! {
+            @match change {
+                Some(change) => {
+                    @let index = if old { change.old_index() } else { change.new_index() };
+                    td.line-no {(line_no(index))}
+                    td class={"line " (tag_class(change.tag()))} { pre {(line(change))} }
+                }
+                None => {
+                    td.line-no {}
+                    td.line.empty {}
+                }
+            }
+        }
+    };
+    html! {
+        table.diff.split {
+            @for group in diff.grouped_ops(context) {
+                tr.hunk { td colspan="4" {(hunk_header(&group))} }
+                @for op in &group {
+                    @let changes: Vec<_> = diff.iter_inline_changes(op).collect();
+                    @let (old, new): (Vec<_>, Vec<_>) = changes
+                        .iter()
+                        .filter(|c| c.tag() != ChangeTag::Equal)
+                        .partition(|c| c.tag() == ChangeTag::Delete);
+                    @if old.is_empty() && new.is_empty() {
+                        @for change in &changes {
+                            tr { (cell(Some(change), true)) (cell(Some(change), false)) }
+                        }
+                    } @else {
+                        @for i in 0..old.len().max(new.len()) {
+                            tr { (cell(old.get(i).copied(), true)) (cell(new.get(i).copied(), false)) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub async fn diff(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(form): Form<DiffForm>,
+) -> Result<Markup, Error> {
+    let sides = sides(&pool, &cookies, addr, &form).await?;
+    let markup = tokio::task::spawn_blocking(move || {
+        let diff = text_diff(&sides.left.content, &sides.right.content);
+        html! {
+            @if sides.left.content == sides.right.content {
+                p.diff-same {"The Clips are identical."}
+            } @else if form.view == View::Split {
+                (split(&diff, sides.context))
+            } @else {
+                (unified(&diff, sides.context))
+            }
+        }
+    })
+    .await?;
+    Ok(markup)
+}
+
+pub async fn raw(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(form): Form<DiffForm>,
+) -> Result<Response, Error> {
+    let sides = sides(&pool, &cookies, addr, &form).await?;
+    let unified = tokio::task::spawn_blocking(move || {
+        text_diff(&sides.left.content, &sides.right.content)
+            .unified_diff()
+            .context_radius(sides.context)
+            .header(form.left.trim(), form.right.trim())
+            .to_string()
+    })
+    .await?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], unified).into_response())
+}
+
+pub async fn page(State(pool): State<PgPool>, cookies: Cookies) -> Markup {
+    html! {
+        (DOCTYPE);
+        head {
+            title {"Compare Clips"}
+            (HTMX);
+            (CSS("/files/style.css"));
+            (CSS("/files/css/ecb.css"));
+        }
+        body {
+            (nav("/ecb", &cookies, &pool).await);
+            div id="content" {
+                h1 {"Compare Clips"}
+                p {"Refer to clips as r/<code>, n/<name> or p/<name>, or paste their permalinks."}
+                form.diff-form hx-post="/ecb/diff" hx-target="#diff-result" {
+                    fieldset {
+                        legend {"Old"}
+                        input name="left" type="text" placeholder="r/1234" {}
+                        input name="left_password" type="password" placeholder="password, if private" {}
+                    }
+                    fieldset {
+                        legend {"New"}
+                        input name="right" type="text" placeholder="n/config" {}
+                        input name="right_password" type="password" placeholder="password, if private" {}
+                    }
+                    select name="view" {
+                        option value="unified" {"unified"}
+                        option value="split" {"side by side"}
+                    }
+                    button {"compare"}
+                }
+                div #"diff-result" {}
+            }
+        }
+    }
+}
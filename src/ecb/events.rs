@@ -0,0 +1,86 @@
+use super::*;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use sqlx::postgres::PgListener;
+use std::{convert::Infallible, sync::OnceLock, time::Duration};
+use tokio::sync::broadcast;
+
+// Postgres channel carrying the name of every named clip that was written,
+// so all instances sharing the database can fan out to their own subscribers
+const CHANNEL: &str = "ecb_named";
+const RETRY: Duration = Duration::from_secs(1);
+
+static HUB: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+
+fn subscribe(pool: &PgPool) -> broadcast::Receiver<String> {
+    HUB.get_or_init(|| {
+        let (tx, _) = broadcast::channel(64);
+        tokio::spawn(listen(pool.clone(), tx.clone()));
+        tx
+    })
+    .subscribe()
+}
+
+async fn listen(pool: PgPool, tx: broadcast::Sender<String>) {
+    loop {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("ecb events: can't connect listener: {e}");
+                tokio::time::sleep(RETRY).await;
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(CHANNEL).await {
+            eprintln!("ecb events: can't listen on {CHANNEL}: {e}");
+            tokio::time::sleep(RETRY).await;
+            continue;
+        }
+        loop {
+            match listener.recv().await {
+                // no subscribers is fine, the next one will get the next write
+                Ok(notification) => _ = tx.send(notification.payload().to_owned()),
+                Err(e) => {
+                    eprintln!("ecb events: listener failed: {e}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+pub async fn notify_named(pool: &PgPool, name: &str) -> Result<(), Error> {
+    sqlx::query!("SELECT pg_notify($1, $2)", CHANNEL, name)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub fn named_path(name: &str) -> String {
+    format!("/ecb/named/{}/events", urlencoding::encode(name))
+}
+
+pub async fn named(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = subscribe(&pool);
+    let stream = stream::unfold((rx, pool, name), |(mut rx, pool, name)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(written) if written != name => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+                // either our clip was written or we lagged and may have missed it
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            }
+            let Ok(clip) = get_named(&pool, &name).await else {
+                continue;
+            };
+            // SSE data lines can't carry carriage returns
+            let body = clip.render().into_string().replace('\r', "");
+            let event = Event::default().event("clip").data(body);
+            return Some((Ok(event), (rx, pool, name)));
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
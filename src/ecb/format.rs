@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use maud::*;
+use std::sync::LazyLock;
+use syntect::{highlighting::ThemeSet, parsing::SyntaxSet};
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+const THEME: &str = "InspiredGitHub";
+
+// (line start, syntax token) pairs tried in order when no language was
+// chosen and the first line says nothing
+const DETECT: &[(&str, &str)] = &[
+    ("<!DOCTYPE", "html"),
+    ("<html", "html"),
+    ("#include", "c"),
+    ("fn main(", "rs"),
+    ("let mut ", "rs"),
+    ("def ", "py"),
+    ("import ", "py"),
+    ("function ", "js"),
+    ("const ", "js"),
+    ("SELECT ", "sql"),
+    ("CREATE TABLE", "sql"),
+    ("[package]", "toml"),
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Plain,
+    Markdown,
+    Code,
+}
+
+impl Format {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Format::Plain => "plain",
+            Format::Markdown => "markdown",
+            Format::Code => "code",
+        }
+    }
+}
+
+impl From<String> for Format {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "markdown" => Format::Markdown,
+            "code" => Format::Code,
+            _ => Format::Plain,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Clip {
+    pub content: String,
+    pub format: Format,
+    pub language: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Clip {
+    pub fn new(content: String, format: String, language: Option<String>) -> Self {
+        Clip {
+            content,
+            format: format.into(),
+            language,
+            ..Default::default()
+        }
+    }
+
+    pub fn stamped(self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) -> Self {
+        Clip {
+            created_at: Some(created_at),
+            updated_at: Some(updated_at),
+            ..self
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.format, &self.language) {
+            (Format::Code, Some(lang)) => format!("code: {lang}"),
+            (f, _) => f.as_str().to_owned(),
+        }
+    }
+}
+
+impl Render for Clip {
+    fn render(&self) -> Markup {
+        match self.format {
+            Format::Plain => plain(&self.content),
+            Format::Markdown => markdown(&self.content),
+            Format::Code => code(&self.content, self.language.as_deref()),
+        }
+    }
+}
+
+/// Empty form fields come in as `Some("")`
+pub fn language(language: Option<String>) -> Option<String> {
+    language
+        .map(|l| l.trim().to_owned())
+        .filter(|l| !l.is_empty())
+}
+
+fn plain(content: &str) -> Markup {
+    html! { pre.clip-plain { (content) } }
+}
+
+fn markdown(content: &str) -> Markup {
+    use pulldown_cmark::{html::push_html, Options, Parser};
+    let parser = Parser::new_ext(
+        content,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS,
+    );
+    let mut out = String::new();
+    push_html(&mut out, parser);
+    html! { div.clip-markdown { (PreEscaped(ammonia::clean(&out))) } }
+}
+
+fn code(content: &str, language: Option<&str>) -> Markup {
+    let syntax = language
+        .and_then(|l| SYNTAXES.find_syntax_by_token(l))
+        .or_else(|| SYNTAXES.find_syntax_by_first_line(content))
+        .or_else(|| {
+            DETECT
+                .iter()
+                .find(|(start, _)| content.lines().any(|l| l.starts_with(start)))
+                .and_then(|(_, token)| SYNTAXES.find_syntax_by_token(token))
+        })
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+    let theme = &THEMES.themes[THEME];
+    match syntect::html::highlighted_html_for_string(content, &SYNTAXES, syntax, theme) {
+        Ok(out) => html! { div.clip-code { (PreEscaped(out)) } },
+        Err(_) => plain(content),
+    }
+}
+
+pub fn inputs() -> Markup {
+    html! {
+        select name="format" {
+            option value="plain" selected {"Plain"}
+            option value="markdown" {"Markdown"}
+            option value="code" {"Code"}
+        }
+        select name="language" {
+            option value="" selected {"detect language"}
+            @for (name, token) in languages() {
+                option value=(token) {(name)}
+            }
+        }
+    }
+}
+
+/// Name and token of every syntax code can be highlighted with
+fn languages() -> Vec<(&'static str, &'static str)> {
+    let mut languages: Vec<_> = SYNTAXES
+        .syntaxes()
+        .iter()
+        .filter_map(|s| Some((s.name.as_str(), s.file_extensions.first()?.as_str())))
+        .collect();
+    languages.sort_by_key(|(name, _)| name.to_lowercase());
+    languages
+}
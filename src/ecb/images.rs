@@ -0,0 +1,201 @@
+//! Image clips, mostly screenshots pasted into the `/ecb` textarea.
+//!
+//! Uploads are decoded once to check them and to build a png thumbnail;
+//! the original bytes are served back untouched.
+use super::*;
+use axum::{
+    body::Bytes,
+    http::header,
+    response::Response,
+};
+use image::{ImageFormat, ImageReader};
+use limits::LIMITS;
+use std::io::Cursor;
+
+const THUMB_SIZE: u32 = 320;
+const FORMATS: &[ImageFormat] = &[
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+];
+
+struct Decoded {
+    mime: &'static str,
+    width: u32,
+    height: u32,
+    thumbnail: Vec<u8>,
+}
+
+// checks the dimensions from the header before decoding, so a tiny file
+// can't expand into gigabytes of pixels
+fn decode(data: &[u8]) -> Result<Decoded, Error> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let format = reader
+        .format()
+        .filter(|f| FORMATS.contains(f))
+        .ok_or(UnsupportedImage)?;
+    let (width, height) = reader.into_dimensions()?;
+    let pixels = width as u64 * height as u64;
+    if pixels > LIMITS.max_pixels {
+        return Err(TooManyPixels(LIMITS.max_pixels));
+    }
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(width);
+    limits.max_image_height = Some(height);
+    reader.limits(limits);
+    let thumb = reader.decode()?.thumbnail(THUMB_SIZE, THUMB_SIZE);
+    let mut thumbnail = Cursor::new(Vec::new());
+    thumb.write_to(&mut thumbnail, ImageFormat::Png)?;
+    Ok(Decoded {
+        mime: format.to_mime_type(),
+        width,
+        height,
+        thumbnail: thumbnail.into_inner(),
+    })
+}
+
+pub fn image_path(id: i32) -> String {
+    format!("/ecb/i/{id}")
+}
+
+struct ImageMeta {
+    width: i32,
+    height: i32,
+    created_at: chrono::DateTime<chrono::Utc>,
+    hidden: bool,
+}
+
+async fn get_meta(pool: &PgPool, id: i32) -> Result<ImageMeta, Error> {
+    let meta = sqlx::query_as!(
+        ImageMeta,
+        "SELECT width, height, created_at, hidden FROM ecb.images WHERE id=$1",
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .or(Err(ImageNotFound(id)))?;
+    if meta.hidden {
+        return Err(HiddenClip(format!("image #{id}")));
+    }
+    Ok(meta)
+}
+
+fn preview(id: i32) -> Markup {
+    let path = image_path(id);
+    html! {
+        a href=(format!("{path}/raw")) {
+            img.clip-image src=(format!("{path}/thumb")) alt=(format!("image clip #{id}")) {}
+        }
+    }
+}
+
+/// Stores a raw image body, the proof of work comes in the query string
+pub async fn upload(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Query(pow): Query<limits::Pow>,
+    body: Bytes,
+) -> Result<Markup, Error> {
+    if body.is_empty() {
+        return Err(EmptyClip);
+    }
+    if body.len() > LIMITS.max_image {
+        return Err(ContentTooLarge("image", LIMITS.max_image));
+    }
+    // before decoding, so it can't be used to keep the blocking pool busy
+    moderation::check_blocked(&pool, &body).await?;
+    limits::admit(&pool, addr, &cookies, &pow).await?;
+    let data = body.clone();
+    let decoded = tokio::task::spawn_blocking(move || decode(&data)).await??;
+    let id = sqlx::query!("
+INSERT INTO ecb.images (mime, width, height, data, thumbnail)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING id
+", decoded.mime, decoded.width as i32, decoded.height as i32, body.as_ref(), decoded.thumbnail)
+        .fetch_one(&pool)
+        .await?
+        .id;
+    let path = image_path(id);
+    Ok(html! {
+        fieldset #"swap" {
+            legend {"Image #" (id) " (" (decoded.width) "×" (decoded.height) ")"}
+            div.clip-body {(preview(id))}
+            (qr::tag(&path))
+            a.permalink href=(path) {"permalink"}
+            " "
+            a.raw href=(format!("{path}/raw")) {"raw"}
+        }
+    })
+}
+
+pub async fn raw(State(pool): State<PgPool>, Path(id): Path<i32>) -> Result<Response, Error> {
+    let row = sqlx::query!("SELECT mime, data, hidden FROM ecb.images WHERE id=$1", id)
+        .fetch_one(&pool)
+        .await
+        .or(Err(ImageNotFound(id)))?;
+    if row.hidden {
+        return Err(HiddenClip(format!("image #{id}")));
+    }
+    // the mime type was sniffed from the upload, browsers shouldn't guess another
+    Ok((
+        [
+            (header::CONTENT_TYPE, row.mime.as_str()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        row.data,
+    )
+        .into_response())
+}
+
+/// Hidden images keep their thumbnail for admins, it's what the report queue shows
+pub async fn thumb(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    cookies: Cookies,
+) -> Result<Response, Error> {
+    let row = sqlx::query!("SELECT thumbnail, hidden FROM ecb.images WHERE id=$1", id)
+        .fetch_one(&pool)
+        .await
+        .or(Err(ImageNotFound(id)))?;
+    if row.hidden && !accounts::is_admin(&cookies, &pool).await.unwrap_or(false) {
+        return Err(HiddenClip(format!("image #{id}")));
+    }
+    Ok(([(header::CONTENT_TYPE, "image/png")], row.thumbnail).into_response())
+}
+
+pub async fn page(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    let meta = get_meta(&pool, id).await?;
+    let path = image_path(id);
+    Ok(html! {
+        (DOCTYPE);
+        head {
+            title {"CLIP: image #" (id)}
+            (CSS("/files/style.css"));
+            (CSS("/files/css/ecb.css"));
+        }
+        body {
+            (nav("/ecb", &cookies, &pool).await);
+            div id="content" {
+                h1 {"CLIP: image #" (id)}
+                dl.clip-meta {
+                    dt {"Size"} dd {(meta.width) "×" (meta.height)}
+                    dt {"Created"} dd {(meta.created_at.format("%Y-%m-%d %H:%M UTC"))}
+                }
+                (qr::tag(&path))
+                fieldset.clip-page {
+                    a href=(format!("{path}/raw")) {
+                        img.clip-image.full src=(format!("{path}/raw")) alt=(format!("image clip #{id}")) {}
+                    }
+                }
+                (moderation::report_form(moderation::Kind::Image, &id.to_string()))
+            }
+        }
+    })
+}
@@ -0,0 +1,239 @@
+use super::*;
+use std::{net::SocketAddr, str::FromStr, sync::LazyLock};
+
+// names that are or may become ecb routes or namespaces
+const RESERVED_NAMES: &[&str] = &[
+    "admin", "api", "challenge", "diff", "events", "image", "named", "private", "qr", "random",
+    "raw", "search",
+];
+const NAME_CHARS: &str = "letters, digits, '-', '_' and '.'";
+const CHALLENGE_TTL: i64 = 10 * 60;
+
+/// Read once from the environment, see `.env.example`
+pub struct Limits {
+    pub max_random: usize,
+    pub max_named: usize,
+    pub max_private: usize,
+    pub max_name: usize,
+    pub max_image: usize,
+    /// width × height an uploaded image may decode to
+    pub max_pixels: u64,
+    /// clips one IP may create per `quota_window` seconds
+    pub quota: i64,
+    pub quota_window: i64,
+    /// leading zero bits of the proof-of-work hash, 0 disables it
+    pub pow_difficulty: u32,
+}
+
+pub fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl Limits {
+    fn from_env() -> Self {
+        Limits {
+            max_random: env_or("ECB_MAX_RANDOM", 64 * 1024),
+            max_named: env_or("ECB_MAX_NAMED", 64 * 1024),
+            max_private: env_or("ECB_MAX_PRIVATE", 64 * 1024),
+            max_name: env_or("ECB_MAX_NAME", 64),
+            max_image: env_or("ECB_MAX_IMAGE", 8 * 1024 * 1024),
+            max_pixels: env_or("ECB_MAX_PIXELS", 25_000_000),
+            quota: env_or("ECB_QUOTA", 60),
+            quota_window: env_or("ECB_QUOTA_WINDOW", 60 * 60),
+            pow_difficulty: env_or("ECB_POW_DIFFICULTY", 0),
+        }
+    }
+}
+
+pub static LIMITS: LazyLock<Limits> = LazyLock::new(Limits::from_env);
+
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Random,
+    Named,
+    Private,
+}
+
+impl Kind {
+    fn max(&self) -> usize {
+        match self {
+            Kind::Random => LIMITS.max_random,
+            Kind::Named => LIMITS.max_named,
+            Kind::Private => LIMITS.max_private,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Random => "random",
+            Kind::Named => "named",
+            Kind::Private => "private",
+        }
+    }
+}
+
+pub fn check_size(kind: Kind, content: &str) -> Result<(), Error> {
+    let max = kind.max();
+    if content.len() > max {
+        return Err(ContentTooLarge(kind.as_str(), max));
+    }
+    Ok(())
+}
+
+pub fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.len() > LIMITS.max_name {
+        return Err(InvalidName(format!(
+            "must be between 1 and {} characters long",
+            LIMITS.max_name
+        )));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(InvalidName(format!("may only contain {NAME_CHARS}")));
+    }
+    if RESERVED_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+        return Err(ReservedName(name.to_owned()));
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct Challenge {
+    salt: Uuid,
+    issued: i64,
+}
+
+/// Proof-of-work fields sent along with anonymous clips
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Pow {
+    pow_challenge: Option<String>,
+    pow_nonce: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ChallengeResponse {
+    challenge: String,
+    difficulty: u32,
+}
+
+pub async fn challenge() -> Result<Json<ChallengeResponse>, Error> {
+    let challenge = jwt::sign(Challenge {
+        salt: Uuid::new_v4(),
+        issued: chrono::Utc::now().timestamp(),
+    })
+    .or(Err(InvalidProofOfWork))?;
+    Ok(Json(ChallengeResponse {
+        challenge,
+        difficulty: LIMITS.pow_difficulty,
+    }))
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Checks the solved challenge and hands back its salt, which can only be spent once
+fn verify_pow(pow: &Pow) -> Result<Uuid, Error> {
+    let filled = |f: &Option<String>| f.clone().filter(|v| !v.is_empty());
+    let (Some(challenge), Some(nonce)) = (filled(&pow.pow_challenge), filled(&pow.pow_nonce)) else {
+        return Err(ProofOfWorkRequired);
+    };
+    let info: Challenge = jwt::verify(&challenge).or(Err(InvalidProofOfWork))?;
+    if chrono::Utc::now().timestamp() - info.issued > CHALLENGE_TTL {
+        return Err(InvalidProofOfWork);
+    }
+    let hash = openssl::sha::sha256(format!("{challenge}:{nonce}").as_bytes());
+    if leading_zero_bits(&hash) < LIMITS.pow_difficulty {
+        return Err(InvalidProofOfWork);
+    }
+    Ok(info.salt)
+}
+
+/// Counts a creation against `ip`'s quota, failing once it's used up
+pub async fn spend_quota(pool: &PgPool, ip: &str, challenge: Option<Uuid>) -> Result<(), Error> {
+    let window = LIMITS.quota_window as f64;
+    let mut tx = pool.begin().await?;
+    // concurrent creations from one IP would all pass the count otherwise
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtext($1))", ip)
+        .execute(&mut *tx)
+        .await?;
+    let used = sqlx::query!(r#"
+SELECT COUNT(*) as "count!"
+FROM ecb.creations
+WHERE ip=$1 AND created_at > now() - make_interval(secs => $2)
+"#, ip, window)
+        .fetch_one(&mut *tx)
+        .await?
+        .count;
+    if used >= LIMITS.quota {
+        return Err(QuotaExceeded);
+    }
+    let inserted = sqlx::query!("
+INSERT INTO ecb.creations (ip, challenge)
+VALUES ($1, $2)
+ON CONFLICT (challenge) DO NOTHING
+", ip, challenge)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    // the challenge was already spent
+    if inserted == 0 {
+        return Err(InvalidProofOfWork);
+    }
+    // spent challenges are kept for as long as they could still be verified
+    let retention = LIMITS.quota_window.max(CHALLENGE_TTL) as f64;
+    sqlx::query!("
+DELETE FROM ecb.creations
+WHERE ip=$1 AND created_at < now() - make_interval(secs => $2)
+", ip, retention)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Gate for every clip created through the web forms
+pub async fn admit(
+    pool: &PgPool,
+    addr: SocketAddr,
+    cookies: &Cookies,
+    pow: &Pow,
+) -> Result<(), Error> {
+    let anonymous = accounts::get_id(cookies).is_err();
+    let challenge = if anonymous && LIMITS.pow_difficulty > 0 {
+        Some(verify_pow(pow)?)
+    } else {
+        None
+    };
+    spend_quota(pool, &addr.ip().to_string(), challenge).await
+}
+
+/// Tells ecb.js to solve a challenge before anonymous posts
+pub fn pow_marker(cookies: &Cookies) -> Markup {
+    let anonymous = accounts::get_id(cookies).is_err();
+    html! {
+        @if anonymous && LIMITS.pow_difficulty > 0 {
+            div #"pow" data-difficulty=(LIMITS.pow_difficulty) {}
+        }
+    }
+}
+
+pub fn pow_inputs() -> Markup {
+    html! {
+        input type="hidden" name="pow_challenge" {}
+        input type="hidden" name="pow_nonce" {}
+    }
+}
@@ -0,0 +1,749 @@
+use self::Error::*;
+use crate::*;
+use axum::{
+    extract::{DefaultBodyLimit, *},
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use maud::*;
+use std::net::SocketAddr;
+
+pub mod diff;
+pub mod events;
+pub mod format;
+pub mod images;
+pub mod limits;
+pub mod moderation;
+pub mod namespace;
+pub mod page;
+pub mod paste;
+pub mod qr;
+pub mod search;
+pub mod share;
+pub mod stats;
+pub mod transfer;
+use format::{Clip, Format};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("No such Clip #{0}")]
+    NotFoundError(i32),
+    #[error("No such Clip Named \"{0}\"")]
+    NameNotFoundError(String),
+    #[error("You can't access Clip #{0}")]
+    UnauthClip(i32),
+    #[error("You can't access Clip \"{0}\"")]
+    UnauthPrivate(String),
+    #[error("No account or group named \"{0}\"")]
+    NoSuchPrincipal(String),
+    #[error("No namespace \"{0}\"")]
+    NamespaceNotFound(String),
+    #[error("Only {0} can write Clips in namespace \"{0}\"")]
+    UnauthNamespace(String),
+    #[error(transparent)]
+    AccountError(#[from] accounts::Error),
+    #[error(transparent)]
+    SqlxError(#[from] sqlx::Error),
+    #[error(transparent)]
+    CryptError(#[from] crypt::Error),
+    #[error("Can't decrypt Clip, possibly wrong password")]
+    FailedDecryption,
+    #[error(transparent)]
+    QrError(#[from] qrcode::types::QrError),
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+    #[error("A {0} Clip can't be larger than {1} bytes")]
+    ContentTooLarge(&'static str, usize),
+    #[error("Invalid Clip name: {0}")]
+    InvalidName(String),
+    #[error("Clip name \"{0}\" is reserved")]
+    ReservedName(String),
+    #[error("Too many Clips created, try again later")]
+    QuotaExceeded,
+    #[error("Anonymous Clips need a proof of work, enable JavaScript or log in")]
+    ProofOfWorkRequired,
+    #[error("Invalid or expired proof of work")]
+    InvalidProofOfWork,
+    #[error("Clip content must be UTF-8 text")]
+    NotText,
+    #[error("Clip is empty")]
+    EmptyClip,
+    #[error("Took too long to send the Clip")]
+    TooSlow,
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error("No such image Clip #{0}")]
+    ImageNotFound(i32),
+    #[error("Images must be png, jpeg, gif or webp")]
+    UnsupportedImage,
+    #[error("An image Clip can't have more than {0} pixels")]
+    TooManyPixels(u64),
+    #[error(transparent)]
+    JoinError(#[from] tokio::task::JoinError),
+    #[error("Clip {0} was hidden by a moderator")]
+    HiddenClip(String),
+    #[error("This content was blocked by a moderator")]
+    BlockedContent,
+    #[error("Invalid report: {0}")]
+    InvalidReport(String),
+    #[error("Only admins can do that")]
+    NotAdmin,
+    #[error("Invalid Clip reference \"{0}\", use r/<code>, n/<name> or p/<name>")]
+    InvalidClipRef(String),
+    #[error("Only Clips up to {0} bytes and {1} lines can be compared")]
+    TooLargeToDiff(usize, usize),
+}
+
+pub fn service() -> Router<PgPool> {
+    Router::new()
+        .route("/", get(index))
+        .route("/random", post(send_random))
+        .route("/random", get(query_random))
+        .route("/random/raw", get(query_random_raw))
+        .route("/named", post(send_named))
+        .route("/named", get(query_named))
+        .route("/named/raw", get(query_named_raw))
+        .route("/named/:name/events", get(events::named))
+        .route("/private", post(send_private))
+        .route("/private", get(query_private))
+        .route("/private/raw", get(query_private_raw))
+        .route("/challenge", get(limits::challenge))
+        .route("/search", get(search::search))
+        .route("/diff", get(diff::page))
+        .route("/diff", post(diff::diff))
+        .route("/diff/raw", post(diff::raw))
+        .route("/ns/:account", get(namespace::list))
+        .route(
+            "/image",
+            post(images::upload).layer(DefaultBodyLimit::max(limits::LIMITS.max_image)),
+        )
+        .route("/i/:id", get(images::page))
+        .route("/i/:id/raw", get(images::raw))
+        .route("/i/:id/thumb", get(images::thumb))
+        .route("/i/:id/qr", get(qr::image))
+        .route("/report", post(moderation::report))
+        .route("/admin/reports", get(moderation::queue))
+        .route("/admin/reports", post(moderation::moderate))
+        .route("/export", get(transfer::export))
+        .route(
+            "/import",
+            post(transfer::import).layer(DefaultBodyLimit::max(*transfer::MAX_IMPORT)),
+        )
+        .route("/r/:code", get(page::random))
+        .route("/n/:name", get(page::named))
+        .route("/p/:name", get(page::private_prompt))
+        .route("/p/:name", post(page::private))
+        .route("/p/:name/share", get(share::page))
+        .route("/p/:name/share", post(share::grant))
+        .route("/p/:name/share/:id/revoke", post(share::revoke))
+        .route("/r/:code/qr", get(qr::random))
+        .route("/n/:name/qr", get(qr::named))
+        .route("/p/:name/qr", get(qr::private))
+}
+
+pub fn get_nav(
+    url: &str,
+) -> Markup {
+    let selected = url.starts_with("/ecb");
+    html!{
+        span {
+            a."current-page"[selected] href="/ecb" {"EasyClipBoard"}
+        }
+    }
+}
+
+impl DescribeError for Error {
+    fn describe(&self) -> (axum::http::StatusCode, String) {
+        use axum::http::StatusCode;
+        let code = match self {
+            AccountError(e) => return e.describe(),
+            UnauthPrivate(_) | UnauthNamespace(_) | NotAdmin => StatusCode::FORBIDDEN,
+            HiddenClip(_) => StatusCode::GONE,
+            NamespaceNotFound(_) => StatusCode::NOT_FOUND,
+            NoSuchPrincipal(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ContentTooLarge(..) | TooManyPixels(_) | TooLargeToDiff(..) => StatusCode::PAYLOAD_TOO_LARGE,
+            UnsupportedImage => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            InvalidName(_)
+            | ReservedName(_)
+            | ProofOfWorkRequired
+            | InvalidProofOfWork
+            | NotText
+            | EmptyClip
+            | BlockedContent
+            | InvalidReport(_)
+            | InvalidClipRef(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            TooSlow => StatusCode::REQUEST_TIMEOUT,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (code, self.to_string())
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let (code, desc) = self.describe();
+        (code, html! {
+            h1 { "EasyClipBoard error" };
+            h2 { (desc) };
+        }).into_response()
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ECBSend {
+    content: String,
+    #[serde(default)]
+    format: Format,
+    language: Option<String>,
+    #[serde(flatten)]
+    pow: limits::Pow,
+}
+#[derive(serde::Deserialize, Debug)]
+struct ECBGet {
+    code: i32,
+}
+
+fn clip_swap(
+    legend: Markup,
+    clip: &Clip,
+    permalink: String,
+    raw: Option<String>,
+    events: Option<String>,
+) -> Markup {
+    html! {
+        fieldset #"swap" data-events=[events] {
+            legend {(legend) " (" (clip.describe()) ")"}
+            div.clip-body {(clip)}
+            (qr::tag(&permalink))
+            a.permalink href=(permalink) {"permalink"}
+            @if let Some(raw) = raw {
+                " "
+                a.raw href=(raw) {"raw"}
+            }
+        }
+    }
+}
+
+async fn get_random(pool: &PgPool, code: i32) -> Result<Clip, Error> {
+    let r = sqlx::query!(
+        "
+SELECT content, format, language, created_at, updated_at, hidden
+FROM ecb.random
+WHERE id=$1
+",
+        code
+    )
+    .fetch_one(pool)
+    .await
+    .or(Err(NotFoundError(code)))?;
+    if r.hidden {
+        return Err(HiddenClip(format!("#{code}")));
+    }
+    Ok(Clip::new(r.content, r.format, r.language).stamped(r.created_at, r.updated_at))
+}
+
+async fn query_random(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<ECBGet>,
+) -> Result<Markup, Error> {
+    let code = params.code;
+    let clip = get_random(&pool, code).await?;
+    stats::record(&pool, limits::Kind::Random, &code.to_string(), addr, true);
+    Ok(clip_swap(
+        html! {"CLIP: #"(code)},
+        &clip,
+        page::random_path(code),
+        Some(format!("/ecb/random/raw?code={code}")),
+        None,
+    ))
+}
+
+async fn query_random_raw(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<ECBGet>,
+) -> Result<String, Error> {
+    let clip = get_random(&pool, params.code).await?;
+    stats::record(&pool, limits::Kind::Random, &params.code.to_string(), addr, true);
+    Ok(clip.content)
+}
+
+/// Stores a random clip under the code derived from its content
+async fn store_random(pool: &PgPool, clip: &Clip) -> Result<i32, Error> {
+    moderation::check_blocked(pool, &clip.content).await?;
+    let code = (fxhash::hash64(&clip.content) % 10000) as i32;
+    // a new clip taking over the code isn't the one that was hidden
+    sqlx::query!(
+        "
+INSERT INTO ecb.random (id, content, format, language)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (id)
+DO UPDATE SET content=$2, format=$3, language=$4, updated_at=now(),
+    hidden=ecb.random.hidden AND ecb.random.content=$2;
+",
+        code,
+        &clip.content,
+        clip.format.as_str(),
+        clip.language,
+    )
+    .execute(pool)
+    .await?;
+    Ok(code)
+}
+
+async fn send_random(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(info): Form<ECBSend>,
+) -> Result<Markup, Error> {
+    limits::check_size(limits::Kind::Random, &info.content)?;
+    limits::admit(&pool, addr, &cookies, &info.pow).await?;
+    let clip = Clip {
+        content: info.content,
+        format: info.format,
+        language: format::language(info.language),
+        ..Default::default()
+    };
+    let code = store_random(&pool, &clip).await?;
+    Ok(clip_swap(
+        html! {"CLIP: #"(code)},
+        &clip,
+        page::random_path(code),
+        Some(format!("/ecb/random/raw?code={code}")),
+        None,
+    ))
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ECBSendNamed {
+    content: String,
+    name: String,
+    #[serde(default)]
+    format: Format,
+    language: Option<String>,
+    // checkbox, present when the clip should stay out of search results
+    unlisted: Option<String>,
+    #[serde(flatten)]
+    pow: limits::Pow,
+}
+#[derive(serde::Deserialize, Debug)]
+struct ECBGetNamed {
+    name: String,
+}
+
+async fn send_named(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(params): Form<ECBSendNamed>,
+) -> Result<Markup, Error> {
+    let (ns, short) = namespace::split(&params.name);
+    limits::check_name(short)?;
+    limits::check_size(limits::Kind::Named, &params.content)?;
+    moderation::check_blocked(&pool, &params.content).await?;
+    let namespace_id = match ns {
+        Some(ns) => Some(namespace::owned(&pool, ns, &cookies).await?),
+        None => None,
+    };
+    limits::admit(&pool, addr, &cookies, &params.pow).await?;
+    let clip = Clip {
+        content: params.content,
+        format: params.format,
+        language: format::language(params.language),
+        ..Default::default()
+    };
+    let searchable = params.unlisted.is_none();
+    // hidden clips stay as the moderator left them, overwriting would unhide them
+    let written = match namespace_id {
+        None => sqlx::query!("
+INSERT INTO ecb.named (name, content, format, language, searchable)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (name) WHERE namespace_id IS NULL
+DO UPDATE SET content=$2, format=$3, language=$4, searchable=$5, updated_at=now()
+WHERE NOT ecb.named.hidden
+", short, &clip.content, clip.format.as_str(), clip.language, searchable)
+            .execute(&pool)
+            .await?,
+        Some(namespace_id) => sqlx::query!("
+INSERT INTO ecb.named (name, content, format, language, searchable, namespace_id)
+VALUES ($1, $2, $3, $4, $5, $6)
+ON CONFLICT (namespace_id, name) WHERE namespace_id IS NOT NULL
+DO UPDATE SET content=$2, format=$3, language=$4, searchable=$5, updated_at=now()
+WHERE NOT ecb.named.hidden
+", short, &clip.content, clip.format.as_str(), clip.language, searchable, namespace_id)
+            .execute(&pool)
+            .await?,
+    }
+    .rows_affected();
+    let name = params.name;
+    if written == 0 {
+        return Err(HiddenClip(format!("\"{name}\"")));
+    }
+    events::notify_named(&pool, &name).await?;
+    Ok(clip_swap(
+        html! {"CLIP: \"" (name) "\""},
+        &clip,
+        page::named_path(&name),
+        Some(format!("/ecb/named/raw?name={}", urlencoding::encode(&name))),
+        Some(events::named_path(&name)),
+    ))
+}
+
+async fn get_named(pool: &PgPool, name: &str) -> Result<Clip, Error> {
+    let (ns, short) = namespace::split(name);
+    let r = sqlx::query!("
+SELECT n.content, n.format, n.language, n.created_at, n.updated_at, n.hidden
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE n.name=$1 AND a.name IS NOT DISTINCT FROM $2;
+", short, ns)
+    .fetch_one(pool)
+    .await
+    .or(Err(NameNotFoundError(name.to_owned())))?;
+    if r.hidden {
+        return Err(HiddenClip(format!("\"{name}\"")));
+    }
+    Ok(Clip::new(r.content, r.format, r.language).stamped(r.created_at, r.updated_at))
+}
+
+async fn query_named(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<ECBGetNamed>,
+) -> Result<Markup, Error> {
+    let name = params.name;
+    let clip = get_named(&pool, &name).await?;
+    stats::record(&pool, limits::Kind::Named, &name, addr, true);
+    Ok(clip_swap(
+        html! {"CLIP: \""(&name) "\""},
+        &clip,
+        page::named_path(&name),
+        Some(format!("/ecb/named/raw?name={}", urlencoding::encode(&name))),
+        Some(events::named_path(&name)),
+    ))
+}
+
+async fn query_named_raw(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<ECBGetNamed>,
+) -> Result<String, Error> {
+    let clip = get_named(&pool, &params.name).await?;
+    stats::record(&pool, limits::Kind::Named, &params.name, addr, true);
+    Ok(clip.content)
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ECBSendPrivate{
+    content: String,
+    name: String,
+    // may be left empty by accounts with write access to a shared clip
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    format: Format,
+    language: Option<String>,
+    #[serde(flatten)]
+    pow: limits::Pow,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ECBGetPrivate {
+    name: String,
+    // may be left empty by accounts the clip is shared with
+    #[serde(default)]
+    password: String,
+}
+
+async fn send_private(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(params): Form<ECBSendPrivate>,
+) -> Result<Markup, Error> {
+    limits::check_name(&params.name)?;
+    limits::check_size(limits::Kind::Private, &params.content)?;
+    limits::admit(&pool, addr, &cookies, &params.pow).await?;
+    let account = accounts::get_id(&cookies).ok();
+    let name = params.name;
+    let shared = params.password.is_empty();
+    let password = match params.password.as_str() {
+        "" => share::shared_password(&pool, &name, account, share::Access::Write)
+            .await?
+            .ok_or(UnauthPrivate(name.clone()))?,
+        password => password.to_owned(),
+    };
+    let content = crypt::encrypt(&params.content, &password)?;
+    let clip = Clip {
+        content: params.content,
+        format: params.format,
+        language: format::language(params.language),
+        ..Default::default()
+    };
+    // owned clips may only be overwritten by their owner and writers, and
+    // writers only with the shared password: one they chose would lock the
+    // owner out and outlive a revoked grant. A password change by the
+    // owner also moves the sealed copy used for sharing
+    let written = sqlx::query!("
+INSERT INTO ecb.private (name, content, format, language, owner_id)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (name)
+DO UPDATE SET
+    content=$2, format=$3, language=$4, updated_at=now(),
+    shared_key=CASE WHEN ecb.private.shared_key IS NULL THEN NULL ELSE $6::bytea END
+WHERE ecb.private.owner_id IS NULL
+    OR ecb.private.owner_id = $5
+    OR ($7 AND ecb.private_access(ecb.private.name, $5) = 'write');
+", name, content, clip.format.as_str(), clip.language, account, crypt::seal(&password)?, shared)
+        .execute(&pool)
+        .await?
+        .rows_affected();
+    if written == 0 {
+        return Err(UnauthPrivate(name));
+    }
+    Ok(clip_swap(
+        html! {"CLIP: #"(name)},
+        &clip,
+        page::private_path(&name),
+        None,
+        None,
+    ))
+}
+
+async fn get_private(pool: &PgPool, name: &str, password: &str) -> Result<Clip, Error> {
+    let enc = sqlx::query!("
+SELECT content, format, language, created_at, updated_at
+FROM ecb.private
+WHERE name=$1;
+", name).fetch_one(pool)
+        .await
+        .or(Err(NameNotFoundError(name.to_owned())))?;
+    let content = crypt::decrypt(enc.content, password)?;
+    let content = String::from_utf8(content).or(Err(FailedDecryption))?;
+    Ok(Clip::new(content, enc.format, enc.language).stamped(enc.created_at, enc.updated_at))
+}
+
+/// Opens a private clip with its password or, when that's empty, through a share.
+/// Reads and wrong passwords are counted in the clip's stats.
+async fn unlock_private(
+    pool: &PgPool,
+    name: &str,
+    password: &str,
+    cookies: &Cookies,
+    addr: SocketAddr,
+) -> Result<Clip, Error> {
+    let clip = if password.is_empty() {
+        let account = accounts::get_id(cookies).ok();
+        let password = share::shared_password(pool, name, account, share::Access::Read)
+            .await?
+            .ok_or(UnauthPrivate(name.to_owned()))?;
+        get_private(pool, name, &password).await
+    } else {
+        get_private(pool, name, password).await
+    };
+    match &clip {
+        Ok(_) => stats::record(pool, limits::Kind::Private, name, addr, true),
+        Err(FailedDecryption | CryptError(_)) => {
+            stats::record(pool, limits::Kind::Private, name, addr, false)
+        }
+        Err(_) => {}
+    }
+    clip
+}
+
+async fn get_private_meta(pool: &PgPool, name: &str) -> Result<Clip, Error> {
+    sqlx::query!("
+SELECT format, language, created_at, updated_at
+FROM ecb.private
+WHERE name=$1;
+", name)
+        .fetch_one(pool)
+        .await
+        .map(|r| Clip::new(String::new(), r.format, r.language).stamped(r.created_at, r.updated_at))
+        .or(Err(NameNotFoundError(name.to_owned())))
+}
+
+async fn query_private(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Query(params): Query<ECBGetPrivate>,
+) -> Result<Markup, Error> {
+    let clip = unlock_private(&pool, &params.name, &params.password, &cookies, addr).await?;
+    Ok(clip_swap(
+        html! {"CLIP: #"(params.name)},
+        &clip,
+        page::private_path(&params.name),
+        None,
+        None,
+    ))
+}
+
+async fn query_private_raw(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Query(params): Query<ECBGetPrivate>,
+) -> Result<String, Error> {
+    Ok(unlock_private(&pool, &params.name, &params.password, &cookies, addr).await?.content)
+}
+
+async fn index(State(pool): State<PgPool>, cookies: Cookies) -> Markup {
+    html! {
+    head {
+        (DOCTYPE);
+        (JS("/files/js/ecb.js"));
+        (HTMX);
+        (CSS("/files/style.css"));
+        (CSS("/files/css/ecb.css"));
+    }
+    body {
+        (nav("/ecb", &cookies, &pool).await);
+        div id="content" {
+        div.ecb-half {
+
+            fieldset {
+                legend {"Select Clip type"}
+                label for="select-random" {
+                    input
+                        value="random" name="select-clip"
+                        type="radio" id="select-random"
+                        checked {}
+                    "Random"
+                }
+                label for="select-named" {
+                    input
+                        value="named" name="select-clip"
+                        type="radio" id="select-named" {}
+                    "Named"
+                }
+                label for="select-private" {
+                    input
+                        value="private" name="select-clip"
+                        type="radio" id="select-private" {}
+                    "Private"
+                }
+            }
+
+            // writers
+            fieldset
+                class="stat send"
+                id="write-random"
+            {
+                legend {"Write - Random"}
+                form
+                    hx-post="/ecb/random"
+                    hx-target="#swap"
+                    hx-swap="innerHTML"
+                {
+                    (format::inputs())
+                    (limits::pow_inputs())
+                    br {}
+                    textarea name="content" placeholder="text, or paste an image" {}
+                    button {"create"}
+                }
+            }
+            fieldset
+                class="stat invisible send"
+                id="write-private"
+            {
+                legend {"Write - Private"}
+                form
+                    hx-post="/ecb/private"
+                    hx-target="#swap"
+                    hx-swap="innerHTML"
+                {
+                    input placeholder="Clip name" name="name" type="text" {}
+                    input placeholder="Clip password" name="password" type="password" {}
+                    br {}
+                    (format::inputs())
+                    (limits::pow_inputs())
+                    br {}
+                    textarea name="content" {}
+                    button {"create"}
+                }
+            }
+            fieldset
+                class="stat invisible send"
+                id="write-named"
+            {
+                legend {"Write - Named"}
+                form
+                    hx-post="/ecb/named"
+                    hx-target="#swap"
+                    hx-swap="innerHTML"
+                {
+                    input placeholder="Clip name or account/name" name="name" type="text" {}
+                    label for="named-unlisted" {
+                        input id="named-unlisted" name="unlisted" type="checkbox" {}
+                        "Hide from search"
+                    }
+                    br {}
+                    (format::inputs())
+                    (limits::pow_inputs())
+                    br {}
+                    textarea name="content" {}
+                    button {"create"}
+                }
+            }
+
+            // readers
+            fieldset
+                class="stat get"
+                id="read-random"
+            {
+                legend {"Read - Random"}
+                form
+                    hx-get="/ecb/random"
+                    hx-target="#swap"
+                    hx-swap="innerHTML"
+                {
+                    input.not-incremental placeholder="code" name="code" type="number" {}
+                    button {"search"}
+                }
+            }
+            fieldset
+                class="stat get invisible"
+                id="read-named"
+            {
+                legend {"Read - Named"}
+                form
+                    hx-get="/ecb/named"
+                    hx-target="#swap"
+                    hx-swap="innerHTML"
+                {
+                    input placeholder="name" name="name" type="text" {}
+                    button {"search"}
+                }
+            }
+            fieldset
+                class="stat get invisible"
+                id="read-private"
+            {
+                legend {"Read - Private"}
+                form
+                    hx-get="/ecb/private"
+                    hx-target="#swap"
+                    hx-swap="innerHTML"
+                {
+                    input placeholder="Clip name" name="name" type="text" {}
+                    input placeholder="Clip password" name="password" type="password" {}
+                    button {"search"}
+                }
+            }
+        }
+        div #swap { }
+        (limits::pow_marker(&cookies))
+        (search::form())
+        p.diff-link { a href="/ecb/diff" {"Compare two clips"} }
+        (namespace::link(&cookies, &pool).await)
+        }
+    }
+    }
+}
@@ -0,0 +1,425 @@
+//! Abuse reports on public clips and images, and the admin queue that
+//! reviews them.
+//!
+//! Moderators can hide a clip, which keeps it around but makes every read
+//! fail with `HiddenClip`, or delete it. Deleting can also block the
+//! content's hash so the same clip can't simply be posted again.
+use super::*;
+use axum::response::Redirect;
+
+const REASONS: &[&str] = &["spam", "malware", "illegal", "personal data", "other"];
+const MAX_DETAILS: usize = 1000;
+
+fn content_hash(content: &[u8]) -> [u8; 32] {
+    openssl::sha::sha256(content)
+}
+
+/// Fails for content a moderator blocked, text or image bytes
+pub async fn check_blocked<'e, E, C>(executor: E, content: C) -> Result<(), Error>
+where
+    E: sqlx::PgExecutor<'e>,
+    C: AsRef<[u8]>,
+{
+    let blocked = sqlx::query!(
+        "SELECT hash FROM ecb.blocklist WHERE hash=$1",
+        &content_hash(content.as_ref())[..]
+    )
+    .fetch_optional(executor)
+    .await?;
+    match blocked {
+        Some(_) => Err(BlockedContent),
+        None => Ok(()),
+    }
+}
+
+/// What can be reported, private clips only ever reach their owners
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Random,
+    Named,
+    Image,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Random => "random",
+            Kind::Named => "named",
+            Kind::Image => "image",
+        }
+    }
+
+    fn from_db(kind: &str) -> Self {
+        match kind {
+            "random" => Kind::Random,
+            "image" => Kind::Image,
+            _ => Kind::Named,
+        }
+    }
+}
+
+enum Content {
+    Text(String),
+    Image(Vec<u8>),
+}
+
+impl Content {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Content::Text(text) => text.as_bytes(),
+            Content::Image(data) => data,
+        }
+    }
+}
+
+pub fn report_form(kind: Kind, clip: &str) -> Markup {
+    html! {
+        details.report {
+            summary {"report"}
+            form method="POST" action="/ecb/report" {
+                input type="hidden" name="kind" value=(kind.as_str()) {}
+                input type="hidden" name="clip" value=(clip) {}
+                select name="reason" {
+                    @for reason in REASONS {
+                        option value=(reason) {(reason)}
+                    }
+                }
+                input type="text" name="details" placeholder="details (optional)" maxlength=(MAX_DETAILS) {}
+                button {"send report"}
+            }
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ReportForm {
+    kind: Kind,
+    clip: String,
+    reason: String,
+    #[serde(default)]
+    details: String,
+}
+
+fn clip_path(kind: Kind, clip: &str) -> String {
+    match kind {
+        Kind::Random => format!("/ecb/r/{clip}"),
+        Kind::Named => page::named_path(clip),
+        Kind::Image => images::image_path(clip.parse().unwrap_or_default()),
+    }
+}
+
+async fn clip_content(pool: &PgPool, kind: Kind, clip: &str) -> Result<Option<Content>, Error> {
+    Ok(match kind {
+        Kind::Random => {
+            let code: i32 = clip.parse().unwrap_or_default();
+            sqlx::query!("SELECT content FROM ecb.random WHERE id=$1", code)
+                .fetch_optional(pool)
+                .await?
+                .map(|r| Content::Text(r.content))
+        }
+        Kind::Named => {
+            let (ns, name) = namespace::split(clip);
+            sqlx::query!("
+SELECT n.content
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE n.name=$1 AND a.name IS NOT DISTINCT FROM $2
+", name, ns)
+                .fetch_optional(pool)
+                .await?
+                .map(|r| Content::Text(r.content))
+        }
+        Kind::Image => {
+            let id: i32 = clip.parse().unwrap_or_default();
+            sqlx::query!("SELECT data FROM ecb.images WHERE id=$1", id)
+                .fetch_optional(pool)
+                .await?
+                .map(|r| Content::Image(r.data))
+        }
+    })
+}
+
+pub async fn report(
+    State(pool): State<PgPool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(form): Form<ReportForm>,
+) -> Result<Markup, Error> {
+    if !REASONS.contains(&form.reason.as_str()) {
+        return Err(InvalidReport(format!("unknown reason \"{}\"", form.reason)));
+    }
+    // hidden clips can still be reported, their readers only see the error
+    if clip_content(&pool, form.kind, &form.clip).await?.is_none() {
+        return Err(InvalidReport(format!("no public Clip \"{}\"", form.clip)));
+    }
+    let details: String = form.details.trim().chars().take(MAX_DETAILS).collect();
+    let reason = if details.is_empty() {
+        form.reason
+    } else {
+        format!("{}: {details}", form.reason)
+    };
+    // repeated reports from one address count once
+    sqlx::query!("
+INSERT INTO ecb.reports (kind, clip, reason, ip, reporter_id)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (kind, clip, ip) WHERE NOT resolved DO NOTHING
+", form.kind.as_str(), &form.clip, reason, addr.ip().to_string(), accounts::get_id(&cookies).ok())
+        .execute(&pool)
+        .await?;
+    Ok(html! {
+        (DOCTYPE);
+        head {
+            title {"Clip reported"}
+            (CSS("/files/style.css"));
+            (CSS("/files/css/ecb.css"));
+        }
+        body {
+            (nav("/ecb", &cookies, &pool).await);
+            div id="content" {
+                h1 {"Thanks for the report"}
+                p {"A moderator will review the Clip."}
+                a href=(clip_path(form.kind, &form.clip)) {"back to clip"}
+            }
+        }
+    })
+}
+
+async fn require_admin(pool: &PgPool, cookies: &Cookies) -> Result<(), Error> {
+    if !accounts::is_admin(cookies, pool).await? {
+        return Err(NotAdmin);
+    }
+    Ok(())
+}
+
+struct Reported {
+    kind: String,
+    clip: String,
+    reports: i64,
+    reasons: Vec<String>,
+    first: chrono::DateTime<chrono::Utc>,
+}
+
+struct Hidden {
+    kind: String,
+    clip: String,
+}
+
+fn actions(kind: &str, clip: &str, hidden: bool) -> Markup {
+    html! {
+        form method="POST" action="/ecb/admin/reports" {
+            input type="hidden" name="kind" value=(kind) {}
+            input type="hidden" name="clip" value=(clip) {}
+            button name="action" value="dismiss" {"dismiss"}
+            @if hidden {
+                button name="action" value="unhide" {"unhide"}
+            } @else {
+                button name="action" value="hide" {"hide"}
+            }
+            button name="action" value="delete" {"delete"}
+            button name="action" value="block" {"delete and block"}
+        }
+    }
+}
+
+pub async fn queue(State(pool): State<PgPool>, cookies: Cookies) -> Result<Markup, Error> {
+    require_admin(&pool, &cookies).await?;
+    let reported = sqlx::query_as!(Reported, r#"
+SELECT
+    kind, clip,
+    COUNT(*) as "reports!",
+    array_agg(reason ORDER BY id) as "reasons!",
+    MIN(created_at) as "first!"
+FROM ecb.reports
+WHERE NOT resolved
+GROUP BY kind, clip
+ORDER BY COUNT(*) DESC, MIN(created_at)
+"#)
+        .fetch_all(&pool)
+        .await?;
+    let hidden = sqlx::query_as!(Hidden, r#"
+SELECT 'random' as "kind!", id::text as "clip!" FROM ecb.random WHERE hidden
+UNION ALL
+SELECT 'named', COALESCE(a.name || '/', '') || n.name
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE n.hidden
+UNION ALL
+SELECT 'image', id::text FROM ecb.images WHERE hidden
+ORDER BY 1, 2
+"#)
+        .fetch_all(&pool)
+        .await?;
+    let is_hidden = |entry: &Reported| {
+        hidden
+            .iter()
+            .any(|h| h.kind == entry.kind && h.clip == entry.clip)
+    };
+    let mut contents = vec![];
+    for entry in &reported {
+        contents.push(clip_content(&pool, Kind::from_db(&entry.kind), &entry.clip).await?);
+    }
+    Ok(html! {
+        (DOCTYPE);
+        head {
+            title {"Reported Clips"}
+            (CSS("/files/style.css"));
+            (CSS("/files/css/ecb.css"));
+        }
+        body {
+            (nav("/ecb", &cookies, &pool).await);
+            div id="content" {
+                h1 {"Reported Clips"}
+                @if reported.is_empty() {
+                    p {"Nothing to review."}
+                }
+                @for (entry, content) in reported.iter().zip(&contents) {
+                    fieldset.report-entry {
+                        legend {
+                            (entry.kind) " "
+                            a href=(clip_path(Kind::from_db(&entry.kind), &entry.clip)) {(entry.clip)}
+                            " · " (entry.reports) " report" @if entry.reports != 1 { "s" } " since "
+                            (entry.first.format("%Y-%m-%d %H:%M UTC"))
+                            @if is_hidden(entry) { " · hidden" }
+                        }
+                        ul {
+                            @for reason in &entry.reasons {
+                                li {(reason)}
+                            }
+                        }
+                        @match content {
+                            Some(Content::Text(content)) => pre.clip-plain {(content)},
+                            Some(Content::Image(_)) => img.clip-image
+                                src=(format!("{}/thumb", clip_path(Kind::Image, &entry.clip)))
+                                alt=(format!("image clip #{}", entry.clip)) {},
+                            None => p {"The Clip no longer exists."},
+                        }
+                        (actions(&entry.kind, &entry.clip, is_hidden(entry)))
+                    }
+                }
+                @if !hidden.is_empty() {
+                    h2 {"Hidden Clips"}
+                    table.hidden-clips {
+                        @for h in &hidden {
+                            tr {
+                                td {(h.kind)}
+                                td { a href=(clip_path(Kind::from_db(&h.kind), &h.clip)) {(h.clip)} }
+                                td {(actions(&h.kind, &h.clip, true))}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Dismiss,
+    Hide,
+    Unhide,
+    Delete,
+    Block,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ModerateForm {
+    kind: Kind,
+    clip: String,
+    action: Action,
+}
+
+async fn set_hidden(pool: &PgPool, kind: Kind, clip: &str, hidden: bool) -> Result<(), Error> {
+    match kind {
+        Kind::Random => {
+            let code: i32 = clip.parse().unwrap_or_default();
+            sqlx::query!("UPDATE ecb.random SET hidden=$2 WHERE id=$1", code, hidden)
+                .execute(pool)
+                .await?;
+        }
+        Kind::Image => {
+            let id: i32 = clip.parse().unwrap_or_default();
+            sqlx::query!("UPDATE ecb.images SET hidden=$2 WHERE id=$1", id, hidden)
+                .execute(pool)
+                .await?;
+        }
+        Kind::Named => {
+            let (ns, name) = namespace::split(clip);
+            sqlx::query!("
+UPDATE ecb.named as n SET hidden=$3
+WHERE n.name=$1 AND (
+    ($2::text IS NULL AND n.namespace_id IS NULL)
+    OR n.namespace_id=(SELECT id FROM inter.accounts WHERE name=$2)
+)
+", name, ns, hidden)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn delete(pool: &PgPool, kind: Kind, clip: &str) -> Result<(), Error> {
+    match kind {
+        Kind::Random => {
+            let code: i32 = clip.parse().unwrap_or_default();
+            sqlx::query!("DELETE FROM ecb.random WHERE id=$1", code)
+                .execute(pool)
+                .await?;
+        }
+        Kind::Image => {
+            let id: i32 = clip.parse().unwrap_or_default();
+            sqlx::query!("DELETE FROM ecb.images WHERE id=$1", id)
+                .execute(pool)
+                .await?;
+        }
+        Kind::Named => {
+            let (ns, name) = namespace::split(clip);
+            sqlx::query!("
+DELETE FROM ecb.named as n
+WHERE n.name=$1 AND (
+    ($2::text IS NULL AND n.namespace_id IS NULL)
+    OR n.namespace_id=(SELECT id FROM inter.accounts WHERE name=$2)
+)
+", name, ns)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn moderate(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    Form(form): Form<ModerateForm>,
+) -> Result<Redirect, Error> {
+    require_admin(&pool, &cookies).await?;
+    match form.action {
+        Action::Dismiss => {}
+        Action::Hide => set_hidden(&pool, form.kind, &form.clip, true).await?,
+        Action::Unhide => set_hidden(&pool, form.kind, &form.clip, false).await?,
+        Action::Delete => delete(&pool, form.kind, &form.clip).await?,
+        Action::Block => {
+            if let Some(content) = clip_content(&pool, form.kind, &form.clip).await? {
+                sqlx::query!(
+                    "INSERT INTO ecb.blocklist (hash) VALUES ($1) ON CONFLICT DO NOTHING",
+                    &content_hash(content.bytes())[..]
+                )
+                .execute(&pool)
+                .await?;
+            }
+            delete(&pool, form.kind, &form.clip).await?;
+        }
+    }
+    sqlx::query!(
+        "UPDATE ecb.reports SET resolved=true WHERE kind=$1 AND clip=$2 AND NOT resolved",
+        form.kind.as_str(),
+        &form.clip
+    )
+    .execute(&pool)
+    .await?;
+    Ok(Redirect::to("/ecb/admin/reports"))
+}
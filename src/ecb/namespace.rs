@@ -0,0 +1,99 @@
+//! Account namespaces for named clips: `alice/todo` lives in alice's
+//! namespace and only alice may write it, while `todo` stays global.
+use super::*;
+
+/// Splits `account/name` into its namespace and clip name
+pub fn split(full: &str) -> (Option<&str>, &str) {
+    match full.rsplit_once('/') {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, full),
+    }
+}
+
+pub fn path(account: &str) -> String {
+    format!("/ecb/ns/{}", urlencoding::encode(account))
+}
+
+/// Id of the namespace's account, which must be the one logged in
+pub async fn owned(pool: &PgPool, namespace: &str, cookies: &Cookies) -> Result<Uuid, Error> {
+    let acc = accounts::get_id(cookies)?;
+    let id = sqlx::query!("SELECT id FROM inter.accounts WHERE name=$1", namespace)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(NamespaceNotFound(namespace.to_owned()))?
+        .id;
+    if id != acc {
+        return Err(UnauthNamespace(namespace.to_owned()));
+    }
+    Ok(id)
+}
+
+pub async fn list(
+    State(pool): State<PgPool>,
+    Path(namespace): Path<String>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    let id = sqlx::query!("SELECT id FROM inter.accounts WHERE name=$1", &namespace)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(NamespaceNotFound(namespace.clone()))?
+        .id;
+    // clips hidden from search are only listed to the namespace owner
+    let own = accounts::get_id(&cookies).is_ok_and(|acc| acc == id);
+    let clips = sqlx::query!("
+SELECT name, format, language, updated_at
+FROM ecb.named
+WHERE namespace_id=$1 AND ((searchable AND NOT hidden) OR $2)
+ORDER BY name
+", id, own)
+        .fetch_all(&pool)
+        .await?;
+    Ok(html! {
+        (DOCTYPE);
+        head {
+            title {"Clips of " (namespace)}
+            (CSS("/files/style.css"));
+            (CSS("/files/css/ecb.css"));
+        }
+        body {
+            (nav("/ecb", &cookies, &pool).await);
+            div id="content" {
+                h1 {"Clips of " (namespace)}
+                @if clips.is_empty() {
+                    p {"No clips yet"}
+                }
+                ul.namespace {
+                    @for clip in &clips {
+                        @let full = format!("{namespace}/{}", clip.name);
+                        @let meta = Clip::new(String::new(), clip.format.clone(), clip.language.clone());
+                        li {
+                            a href=(page::named_path(&full)) {(clip.name)}
+                            " (" (meta.describe()) ") "
+                            small {(clip.updated_at.format("%Y-%m-%d %H:%M UTC"))}
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+pub async fn link(cookies: &Cookies, pool: &PgPool) -> Markup {
+    match accounts::get_acc(cookies, pool).await {
+        Ok(acc) => {
+            let admin = accounts::is_admin(cookies, pool).await.unwrap_or(false);
+            html! {
+                p.namespace-link {
+                    a href=(path(&acc.name)) {"My named clips"}
+                    " · "
+                    a href="/ecb/export" {"Export my clips"}
+                    @if admin {
+                        " · "
+                        a href="/ecb/admin/reports" {"Reported clips"}
+                    }
+                }
+            }
+        }
+        Err(_) => html! {},
+    }
+}
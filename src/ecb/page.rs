@@ -0,0 +1,259 @@
+use super::*;
+use limits::Kind;
+
+const OG_DESCRIPTION_LEN: usize = 200;
+
+pub fn random_path(code: i32) -> String {
+    format!("/ecb/r/{code}")
+}
+
+pub fn named_path(name: &str) -> String {
+    format!("/ecb/n/{}", urlencoding::encode(name))
+}
+
+pub fn private_path(name: &str) -> String {
+    format!("/ecb/p/{}", urlencoding::encode(name))
+}
+
+fn og_description(clip: Option<&Clip>) -> String {
+    match clip {
+        None => "Password protected clip".to_owned(),
+        Some(clip) => {
+            let mut desc: String = clip.content.chars().take(OG_DESCRIPTION_LEN).collect();
+            if clip.content.chars().count() > OG_DESCRIPTION_LEN {
+                desc.push('…');
+            }
+            desc
+        }
+    }
+}
+
+fn copy_button(label: &str, text: &str) -> Markup {
+    html! {
+        button.copy type="button" data-copy=(text) {(label)}
+    }
+}
+
+fn metadata(kind: &str, clip: &Clip, show_content: bool) -> Markup {
+    html! {
+        dl.clip-meta {
+            dt {"Kind"} dd {(kind)}
+            dt {"Format"} dd {(clip.describe())}
+            @if show_content {
+                dt {"Size"}
+                dd {(clip.content.len()) " bytes, " (clip.content.lines().count()) " lines"}
+            }
+            @if let Some(created_at) = clip.created_at {
+                dt {"Created"} dd {(created_at.format("%Y-%m-%d %H:%M UTC"))}
+            }
+            @if let Some(updated_at) = clip.updated_at {
+                dt {"Updated"} dd {(updated_at.format("%Y-%m-%d %H:%M UTC"))}
+            }
+        }
+    }
+}
+
+struct ClipPage<'a> {
+    title: String,
+    kind: &'a str,
+    path: String,
+    host: Option<Host>,
+    clip: &'a Clip,
+    body: Markup,
+    // false for a private clip that wasn't unlocked yet
+    unlocked: bool,
+    events: Option<String>,
+    stats: Markup,
+    // empty for private clips, only public ones can be reported
+    report: Markup,
+}
+
+impl ClipPage<'_> {
+    async fn render(self, cookies: &Cookies, pool: &PgPool) -> Markup {
+        let url = public_url(self.host.as_ref().map(|h| h.0.as_str())) + &self.path;
+        html! {
+            (DOCTYPE);
+            head {
+                title {(self.title)}
+                meta property="og:site_name" content="EasyClipBoard" {}
+                meta property="og:type" content="article" {}
+                meta property="og:title" content=(self.title) {}
+                meta property="og:url" content=(url) {}
+                meta property="og:description" content=(og_description(self.unlocked.then_some(self.clip))) {}
+                (JS("/files/js/ecb.js"));
+                (CSS("/files/style.css"));
+                (CSS("/files/css/ecb.css"));
+            }
+            body {
+                (nav("/ecb", cookies, pool).await);
+                div id="content" {
+                    h1 {(self.title)}
+                    (metadata(self.kind, self.clip, self.unlocked))
+                    (self.stats)
+                    (qr::tag(&self.path))
+                    div.clip-actions {
+                        (copy_button("copy link", &url))
+                        @if self.unlocked {
+                            (copy_button("copy content", &self.clip.content))
+                        }
+                    }
+                    fieldset.clip-page data-events=[self.events] {
+                        legend {(self.title)}
+                        div.clip-body {(self.body)}
+                    }
+                    (self.report)
+                }
+            }
+        }
+    }
+}
+
+pub async fn random(
+    State(pool): State<PgPool>,
+    Path(code): Path<i32>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    host: Option<Host>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    let clip = get_random(&pool, code).await?;
+    stats::record(&pool, Kind::Random, &code.to_string(), addr, true);
+    // random clips have no owner
+    let stats = stats::panel(&pool, Kind::Random, &code.to_string(), false).await?;
+    Ok(ClipPage {
+        title: format!("CLIP: #{code}"),
+        kind: "random",
+        path: random_path(code),
+        host,
+        clip: &clip,
+        body: html! {(clip)},
+        unlocked: true,
+        events: None,
+        stats,
+        report: moderation::report_form(moderation::Kind::Random, &code.to_string()),
+    }
+    .render(&cookies, &pool)
+    .await)
+}
+
+pub async fn named(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    host: Option<Host>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    let clip = get_named(&pool, &name).await?;
+    stats::record(&pool, Kind::Named, &name, addr, true);
+    let (ns, _) = namespace::split(&name);
+    let owner = match ns {
+        Some(ns) => accounts::get_acc(&cookies, &pool)
+            .await
+            .is_ok_and(|acc| acc.name == ns),
+        None => false,
+    };
+    let stats = stats::panel(&pool, Kind::Named, &name, owner).await?;
+    Ok(ClipPage {
+        title: format!("CLIP: \"{name}\""),
+        kind: "named",
+        path: named_path(&name),
+        host,
+        clip: &clip,
+        body: html! {(clip)},
+        unlocked: true,
+        events: Some(events::named_path(&name)),
+        stats,
+        report: moderation::report_form(moderation::Kind::Named, &name),
+    }
+    .render(&cookies, &pool)
+    .await)
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PrivatePassword {
+    #[serde(default)]
+    password: String,
+}
+
+fn password_prompt(name: &str) -> Markup {
+    html! {
+        form.password-prompt method="POST" action=(private_path(name)) {
+            input placeholder="Clip password" name="password" type="password" autofocus {}
+            button {"open"}
+        }
+    }
+}
+
+async fn private_owner(pool: &PgPool, name: &str, cookies: &Cookies) -> Result<bool, Error> {
+    let owner = sqlx::query!("SELECT owner_id FROM ecb.private WHERE name=$1", name)
+        .fetch_one(pool)
+        .await?
+        .owner_id;
+    Ok(owner.is_some() && owner == accounts::get_id(cookies).ok())
+}
+
+pub async fn private_prompt(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    host: Option<Host>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    // accounts the clip is shared with skip the prompt
+    let (clip, unlocked) = match unlock_private(&pool, &name, "", &cookies, addr).await {
+        Ok(clip) => (clip, true),
+        Err(_) => (get_private_meta(&pool, &name).await?, false),
+    };
+    let owner = private_owner(&pool, &name, &cookies).await?;
+    let body = html! {
+        @if unlocked {
+            (clip)
+        } @else {
+            (password_prompt(&name))
+        }
+        @if owner {
+            a.share href=(share::share_path(&name)) {"share"}
+        }
+    };
+    let stats = stats::panel(&pool, Kind::Private, &name, owner).await?;
+    Ok(ClipPage {
+        title: format!("CLIP: #{name}"),
+        kind: "private",
+        path: private_path(&name),
+        host,
+        clip: &clip,
+        body,
+        unlocked,
+        events: None,
+        stats,
+        report: html! {},
+    }
+    .render(&cookies, &pool)
+    .await)
+}
+
+pub async fn private(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    host: Option<Host>,
+    cookies: Cookies,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Form(params): Form<PrivatePassword>,
+) -> Result<Markup, Error> {
+    let clip = unlock_private(&pool, &name, &params.password, &cookies, addr).await?;
+    let owner = private_owner(&pool, &name, &cookies).await?;
+    let stats = stats::panel(&pool, Kind::Private, &name, owner).await?;
+    Ok(ClipPage {
+        title: format!("CLIP: #{name}"),
+        kind: "private",
+        path: private_path(&name),
+        host,
+        clip: &clip,
+        body: html! {(clip)},
+        unlocked: true,
+        events: None,
+        stats,
+        report: html! {},
+    }
+    .render(&cookies, &pool)
+    .await)
+}
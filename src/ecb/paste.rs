@@ -0,0 +1,81 @@
+//! Termbin-style listener: `echo hi | nc host 9999` stores a random clip
+//! and answers with its permalink.
+use super::*;
+use limits::{env_or, LIMITS};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+    time::timeout,
+};
+
+async fn read_all(socket: &mut TcpStream) -> Result<String, Error> {
+    // nc doesn't always close its side, so a quiet client is done sending
+    let idle = Duration::from_secs(env_or("ECB_TCP_IDLE", 2));
+    let max = LIMITS.max_random;
+    let mut content = Vec::new();
+    let mut chunk = [0; 4096];
+    while let Ok(read) = timeout(idle, socket.read(&mut chunk)).await {
+        let n = read?;
+        if n == 0 {
+            break;
+        }
+        content.extend_from_slice(&chunk[..n]);
+        if content.len() > max {
+            return Err(ContentTooLarge(limits::Kind::Random.as_str(), max));
+        }
+    }
+    if content.is_empty() {
+        return Err(EmptyClip);
+    }
+    String::from_utf8(content).or(Err(NotText))
+}
+
+/// Reads the clip, giving slow clients the whole connection `ECB_TCP_DEADLINE`
+async fn receive(socket: &mut TcpStream) -> Result<String, Error> {
+    let deadline = Duration::from_secs(env_or("ECB_TCP_DEADLINE", 30));
+    timeout(deadline, read_all(socket)).await.or(Err(TooSlow))?
+}
+
+async fn paste(pool: &PgPool, socket: &mut TcpStream, peer: SocketAddr) -> Result<i32, Error> {
+    let content = receive(socket).await?;
+    limits::spend_quota(pool, &peer.ip().to_string(), None).await?;
+    let clip = Clip {
+        content,
+        ..Default::default()
+    };
+    store_random(pool, &clip).await
+}
+
+async fn handle(pool: PgPool, mut socket: TcpStream, peer: SocketAddr) {
+    let reply = match paste(&pool, &mut socket, peer).await {
+        Ok(code) => format!("{}{}\n", public_url(None), page::random_path(code)),
+        Err(e) => format!("error: {e}\n"),
+    };
+    if let Err(e) = socket.write_all(reply.as_bytes()).await {
+        eprintln!("ecb paste: can't answer {peer}: {e}");
+    }
+    _ = socket.shutdown().await;
+}
+
+pub async fn serve(pool: PgPool, addr: SocketAddr) -> Result<(), std::io::Error> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("ecb paste listening in {addr}");
+    // further clients wait in the backlog until a connection is done
+    let connections = Arc::new(Semaphore::new(env_or("ECB_TCP_CONNECTIONS", 64)));
+    loop {
+        let permit = connections.clone().acquire_owned().await;
+        let permit = permit.expect("the semaphore is never closed");
+        match listener.accept().await {
+            Ok((socket, peer)) => {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    handle(pool, socket, peer).await;
+                    drop(permit);
+                });
+            }
+            Err(e) => eprintln!("ecb paste: accept failed: {e}"),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+use axum::{http::header, response::Response};
+use qrcode::{render::svg, QrCode};
+
+const QR_SIZE: u32 = 200;
+
+#[derive(serde::Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct QrQuery {
+    #[serde(default)]
+    format: QrFormat,
+}
+
+pub fn tag(permalink: &str) -> Markup {
+    html! {
+        img.qr src=(format!("{permalink}/qr")) alt="QR code for this clip" {}
+    }
+}
+
+fn qr_response(url: &str, format: QrFormat) -> Result<Response, Error> {
+    let code = QrCode::new(url.as_bytes())?;
+    Ok(match format {
+        QrFormat::Svg => {
+            let svg = code
+                .render::<svg::Color>()
+                .min_dimensions(QR_SIZE, QR_SIZE)
+                .build();
+            ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response()
+        }
+        QrFormat::Png => {
+            let img = code
+                .render::<image::Luma<u8>>()
+                .min_dimensions(QR_SIZE, QR_SIZE)
+                .build();
+            let mut png = std::io::Cursor::new(Vec::new());
+            img.write_to(&mut png, image::ImageFormat::Png)?;
+            ([(header::CONTENT_TYPE, "image/png")], png.into_inner()).into_response()
+        }
+    })
+}
+
+fn absolute(host: Option<Host>, path: &str) -> String {
+    public_url(host.as_ref().map(|h| h.0.as_str())) + path
+}
+
+pub async fn random(
+    State(pool): State<PgPool>,
+    Path(code): Path<i32>,
+    Query(query): Query<QrQuery>,
+    host: Option<Host>,
+) -> Result<Response, Error> {
+    get_random(&pool, code).await?;
+    qr_response(&absolute(host, &page::random_path(code)), query.format)
+}
+
+pub async fn named(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    Query(query): Query<QrQuery>,
+    host: Option<Host>,
+) -> Result<Response, Error> {
+    get_named(&pool, &name).await?;
+    qr_response(&absolute(host, &page::named_path(&name)), query.format)
+}
+
+pub async fn private(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    Query(query): Query<QrQuery>,
+    host: Option<Host>,
+) -> Result<Response, Error> {
+    get_private_meta(&pool, &name).await?;
+    qr_response(&absolute(host, &page::private_path(&name)), query.format)
+}
+
+pub async fn image(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(query): Query<QrQuery>,
+    host: Option<Host>,
+) -> Result<Response, Error> {
+    sqlx::query!("SELECT id FROM ecb.images WHERE id=$1", id)
+        .fetch_one(&pool)
+        .await
+        .or(Err(ImageNotFound(id)))?;
+    qr_response(&absolute(host, &images::image_path(id)), query.format)
+}
@@ -0,0 +1,127 @@
+use super::*;
+
+const PAGE_SIZE: i64 = 20;
+// deeper pages are clamped to this one so the offset stays small
+const MAX_PAGE: i64 = 500;
+const SNIPPET_LEN: usize = 160;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct SearchQuery {
+    q: String,
+    #[serde(default)]
+    page: i64,
+}
+
+struct Hit {
+    name: String,
+    snippet: String,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// names are matched by prefix with LIKE, so its wildcards must be taken literally
+fn like_prefix(q: &str) -> String {
+    let mut out = String::with_capacity(q.len() + 1);
+    for c in q.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('%');
+    out
+}
+
+fn snippet(content: &str) -> String {
+    let mut out: String = content.chars().take(SNIPPET_LEN).collect();
+    if content.chars().count() > SNIPPET_LEN {
+        out.push('…');
+    }
+    out
+}
+
+pub async fn search(
+    State(pool): State<PgPool>,
+    Query(params): Query<SearchQuery>,
+) -> Result<Markup, Error> {
+    let q = params.q.trim();
+    if q.is_empty() {
+        return Ok(html! {});
+    }
+    let total = sqlx::query!(r#"
+SELECT COUNT(*) as "total!"
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id,
+websearch_to_tsquery('english', $1) as q
+WHERE n.searchable AND NOT n.hidden AND (
+    n.search @@ q OR n.name LIKE $2 OR a.name || '/' || n.name LIKE $2
+)
+"#, q, like_prefix(q))
+        .fetch_one(&pool)
+        .await?
+        .total;
+    let pages = (total + PAGE_SIZE - 1) / PAGE_SIZE;
+    // pages past the end show the last one
+    let page = params.page.clamp(0, (pages - 1).clamp(0, MAX_PAGE));
+    let rows = sqlx::query!(r#"
+SELECT
+    COALESCE(a.name || '/', '') || n.name as "name!",
+    n.content, n.updated_at
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id,
+websearch_to_tsquery('english', $1) as q
+WHERE n.searchable AND NOT n.hidden AND (
+    n.search @@ q OR n.name LIKE $2 OR a.name || '/' || n.name LIKE $2
+)
+ORDER BY n.name LIKE $2 DESC, ts_rank(n.search, q) DESC, n.name
+LIMIT $3 OFFSET $4
+"#, q, like_prefix(q), PAGE_SIZE, page * PAGE_SIZE)
+        .fetch_all(&pool)
+        .await?;
+    let hits: Vec<Hit> = rows
+        .into_iter()
+        .map(|r| Hit {
+            snippet: snippet(&r.content),
+            name: r.name,
+            updated_at: r.updated_at,
+        })
+        .collect();
+    let page_url = |page: i64| format!("/ecb/search?q={}&page={page}", urlencoding::encode(q));
+    Ok(html! {
+        p.search-count {(total) " clips found"}
+        ul.search-hits {
+            @for hit in &hits {
+                li {
+                    a href=(page::named_path(&hit.name)) {(hit.name)}
+                    " "
+                    small {(hit.updated_at.format("%Y-%m-%d"))}
+                    pre.clip-plain {(hit.snippet)}
+                }
+            }
+        }
+        @if pages > 1 {
+            div.search-pages {
+                @if page > 0 {
+                    button hx-get=(page_url(page - 1)) hx-target="#search-results" {"previous"}
+                }
+                " page " (page + 1) " of " (pages) " "
+                @if page + 1 < pages {
+                    button hx-get=(page_url(page + 1)) hx-target="#search-results" {"next"}
+                }
+            }
+        }
+    })
+}
+
+pub fn form() -> Markup {
+    html! {
+        fieldset.search {
+            legend {"Search named clips"}
+            input
+                type="search" name="q" placeholder="name or content"
+                hx-get="/ecb/search"
+                hx-trigger="input changed delay:300ms, search"
+                hx-target="#search-results" {}
+            div #"search-results" {}
+        }
+    }
+}
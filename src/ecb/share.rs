@@ -0,0 +1,276 @@
+//! Sharing private clips with accounts and meet groups instead of the password.
+//!
+//! Once an owner shares a clip its password is kept sealed with the server
+//! key, so principals with a grant can read (or write) it without knowing it.
+//! Grants are checked on every access, so revoking one applies immediately.
+use super::*;
+use axum::response::Redirect;
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn from_db(access: Option<String>) -> Option<Self> {
+        match access.as_deref() {
+            Some("write") => Some(Access::Write),
+            Some("read") => Some(Access::Read),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Principal {
+    Account,
+    Group,
+}
+
+/// Password of a shared clip, if `account` may use it for `needed`
+pub async fn shared_password(
+    pool: &PgPool,
+    name: &str,
+    account: Option<Uuid>,
+    needed: Access,
+) -> Result<Option<String>, Error> {
+    let Some(account) = account else {
+        return Ok(None);
+    };
+    let Some(row) = sqlx::query!(
+        "SELECT shared_key, ecb.private_access(name, $2) as access FROM ecb.private WHERE name=$1",
+        name,
+        account
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let allowed = Access::from_db(row.access).is_some_and(|a| a >= needed);
+    match row.shared_key {
+        Some(key) if allowed => {
+            let password = crypt::unseal(key)?;
+            Ok(Some(String::from_utf8(password).or(Err(FailedDecryption))?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Seals passwords shared before sealing got its own key and nonce again,
+/// so none depend on the JWT secret; run at startup
+pub async fn reseal_legacy(pool: &PgPool) -> Result<u64, Error> {
+    let shared = sqlx::query!(r#"
+SELECT name, shared_key as "shared_key!"
+FROM ecb.private
+WHERE shared_key IS NOT NULL
+"#)
+        .fetch_all(pool)
+        .await?;
+    let mut resealed = 0;
+    for clip in shared {
+        if crypt::unseal(&clip.shared_key).is_ok() {
+            continue;
+        }
+        let password = crypt::unseal_legacy(&clip.shared_key)?;
+        resealed += sqlx::query!(
+            "UPDATE ecb.private SET shared_key=$3 WHERE name=$1 AND shared_key=$2",
+            clip.name,
+            clip.shared_key,
+            crypt::seal(password)?
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+    }
+    Ok(resealed)
+}
+
+async fn owned(pool: &PgPool, name: &str, cookies: &Cookies) -> Result<Uuid, Error> {
+    let acc = accounts::get_id(cookies)?;
+    let owner = sqlx::query!("SELECT owner_id FROM ecb.private WHERE name=$1", name)
+        .fetch_one(pool)
+        .await
+        .or(Err(NameNotFoundError(name.to_owned())))?
+        .owner_id;
+    if owner != Some(acc) {
+        return Err(UnauthPrivate(name.to_owned()));
+    }
+    Ok(acc)
+}
+
+pub fn share_path(name: &str) -> String {
+    format!("{}/share", page::private_path(name))
+}
+
+struct Grant {
+    id: i32,
+    principal: String,
+    is_group: bool,
+    access: String,
+}
+
+pub async fn page(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    owned(&pool, &name, &cookies).await?;
+    let shared = sqlx::query!(
+        r#"SELECT shared_key IS NOT NULL as "shared!" FROM ecb.private WHERE name=$1"#,
+        &name
+    )
+        .fetch_one(&pool)
+        .await?
+        .shared;
+    let grants = sqlx::query_as!(Grant, r#"
+SELECT
+    g.id,
+    COALESCE(a.name, mg.name) as "principal!",
+    g.group_id IS NOT NULL as "is_group!",
+    g.access
+FROM ecb.private_grants as g
+LEFT JOIN inter.accounts as a ON a.id=g.account_id
+LEFT JOIN meet.groups as mg ON mg.id=g.group_id
+WHERE g.clip_name=$1
+ORDER BY g.id
+"#, &name)
+        .fetch_all(&pool)
+        .await?;
+    let path = share_path(&name);
+    Ok(html! {
+        (DOCTYPE);
+        head {
+            title {"Share CLIP: #" (name)}
+            (CSS("/files/style.css"));
+            (CSS("/files/css/ecb.css"));
+        }
+        body {
+            (nav("/ecb", &cookies, &pool).await);
+            div id="content" {
+                h1 {"Share CLIP: #" (name)}
+                p { a href=(page::private_path(&name)) {"back to clip"} }
+                table.grants {
+                    tr { th {"Who"} th {"Access"} th {} }
+                    @for grant in &grants {
+                        tr {
+                            td {
+                                (grant.principal)
+                                @if grant.is_group { " (group)" }
+                            }
+                            td {(grant.access)}
+                            td {
+                                form method="POST" action=(format!("{path}/{}/revoke", grant.id)) {
+                                    button {"revoke"}
+                                }
+                            }
+                        }
+                    }
+                }
+                form.grant method="POST" action=(path) {
+                    select name="principal" {
+                        option value="account" {"Account"}
+                        option value="group" {"Group"}
+                    }
+                    input name="who" type="text" placeholder="account or group name" {}
+                    select name="access" {
+                        option value="read" {"read"}
+                        option value="write" {"write"}
+                    }
+                    @if !shared {
+                        input name="password" type="password" placeholder="Clip password" {}
+                    }
+                    button {"share"}
+                }
+            }
+        }
+    })
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct GrantForm {
+    principal: Principal,
+    who: String,
+    access: Access,
+    #[serde(default)]
+    password: String,
+}
+
+pub async fn grant(
+    State(pool): State<PgPool>,
+    Path(name): Path<String>,
+    cookies: Cookies,
+    Form(form): Form<GrantForm>,
+) -> Result<Redirect, Error> {
+    owned(&pool, &name, &cookies).await?;
+    let shared_key = sqlx::query!("SELECT shared_key FROM ecb.private WHERE name=$1", &name)
+        .fetch_one(&pool)
+        .await?
+        .shared_key;
+    if shared_key.is_none() {
+        // first share: prove the password before the server keeps it
+        get_private(&pool, &name, &form.password).await?;
+        sqlx::query!(
+            "UPDATE ecb.private SET shared_key=$2 WHERE name=$1",
+            &name,
+            crypt::seal(&form.password)?
+        )
+        .execute(&pool)
+        .await?;
+    }
+    let who = form.who.trim();
+    let access = form.access.as_str();
+    match form.principal {
+        Principal::Account => {
+            let id = sqlx::query!("SELECT id FROM inter.accounts WHERE name=$1", who)
+                .fetch_optional(&pool)
+                .await?
+                .ok_or(NoSuchPrincipal(who.to_owned()))?
+                .id;
+            sqlx::query!("
+INSERT INTO ecb.private_grants (clip_name, account_id, access)
+VALUES ($1, $2, $3)
+ON CONFLICT (clip_name, account_id) DO UPDATE SET access=$3
+", &name, id, access).execute(&pool).await?;
+        }
+        Principal::Group => {
+            let id = sqlx::query!("SELECT id FROM meet.groups WHERE name=$1", who)
+                .fetch_optional(&pool)
+                .await?
+                .ok_or(NoSuchPrincipal(who.to_owned()))?
+                .id;
+            sqlx::query!("
+INSERT INTO ecb.private_grants (clip_name, group_id, access)
+VALUES ($1, $2, $3)
+ON CONFLICT (clip_name, group_id) DO UPDATE SET access=$3
+", &name, id, access).execute(&pool).await?;
+        }
+    }
+    Ok(Redirect::to(&share_path(&name)))
+}
+
+pub async fn revoke(
+    State(pool): State<PgPool>,
+    Path((name, id)): Path<(String, i32)>,
+    cookies: Cookies,
+) -> Result<Redirect, Error> {
+    owned(&pool, &name, &cookies).await?;
+    sqlx::query!(
+        "DELETE FROM ecb.private_grants WHERE clip_name=$1 AND id=$2",
+        &name,
+        id
+    )
+    .execute(&pool)
+    .await?;
+    Ok(Redirect::to(&share_path(&name)))
+}
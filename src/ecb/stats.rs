@@ -0,0 +1,253 @@
+//! View counters and a capped access log per clip.
+//!
+//! Reads only queue an `Access`, a background task does the writing so
+//! stats never add latency to the query handlers. Only clips with an owner
+//! to read it, namespaced named clips and owned private ones, keep a log.
+use super::*;
+use limits::{env_or, Kind};
+use std::{net::SocketAddr, sync::LazyLock, sync::OnceLock};
+use tokio::sync::mpsc;
+
+// accesses kept per clip, 0 disables the log
+static LOG_CAP: LazyLock<i64> = LazyLock::new(|| env_or("ECB_ACCESS_LOG", 50));
+const QUEUE_SIZE: usize = 1024;
+
+#[derive(Debug)]
+struct Access {
+    kind: Kind,
+    clip: String,
+    ip: String,
+    success: bool,
+}
+
+static QUEUE: OnceLock<mpsc::Sender<Access>> = OnceLock::new();
+
+/// Queues an access to be counted, dropping it if the writer is behind
+pub fn record(pool: &PgPool, kind: Kind, clip: &str, addr: SocketAddr, success: bool) {
+    let tx = QUEUE.get_or_init(|| {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(writer(pool.clone(), rx));
+        tx
+    });
+    _ = tx.try_send(Access {
+        kind,
+        clip: clip.to_owned(),
+        ip: addr.ip().to_string(),
+        success,
+    });
+}
+
+async fn writer(pool: PgPool, mut rx: mpsc::Receiver<Access>) {
+    while let Some(access) = rx.recv().await {
+        if let Err(e) = store(&pool, &access).await {
+            eprintln!("ecb stats: can't store {access:?}: {e}");
+        }
+    }
+}
+
+async fn store(pool: &PgPool, access: &Access) -> Result<(), Error> {
+    if access.success {
+        match access.kind {
+            Kind::Random => {
+                let code: i32 = access.clip.parse().unwrap_or_default();
+                sqlx::query!(
+                    "UPDATE ecb.random SET views=views+1, last_accessed=now() WHERE id=$1",
+                    code
+                )
+                .execute(pool)
+                .await?;
+            }
+            Kind::Named => {
+                let (ns, name) = namespace::split(&access.clip);
+                sqlx::query!("
+UPDATE ecb.named as n SET views=views+1, last_accessed=now()
+WHERE n.name=$1 AND (
+    ($2::text IS NULL AND n.namespace_id IS NULL)
+    OR n.namespace_id=(SELECT id FROM inter.accounts WHERE name=$2)
+)
+", name, ns)
+                    .execute(pool)
+                    .await?;
+            }
+            Kind::Private => {
+                sqlx::query!(
+                    "UPDATE ecb.private SET views=views+1, last_accessed=now() WHERE name=$1",
+                    &access.clip
+                )
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+    if *LOG_CAP > 0 {
+        log(pool, access).await?;
+    }
+    Ok(())
+}
+
+async fn log(pool: &PgPool, access: &Access) -> Result<(), Error> {
+    match access.kind {
+        Kind::Random => {}
+        Kind::Named => {
+            let (Some(ns), name) = namespace::split(&access.clip) else {
+                return Ok(());
+            };
+            let logged = sqlx::query!("
+INSERT INTO ecb.access_log (named_id, ip, success)
+SELECT n.id, $3, $4
+FROM ecb.named as n
+INNER JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE n.name=$1 AND a.name=$2
+RETURNING named_id
+", name, ns, &access.ip, access.success)
+                .fetch_optional(pool)
+                .await?;
+            let Some(named_id) = logged.and_then(|l| l.named_id) else {
+                return Ok(());
+            };
+            sqlx::query!("
+DELETE FROM ecb.access_log
+WHERE named_id=$1 AND id < (
+    SELECT MIN(id) FROM (
+        SELECT id FROM ecb.access_log
+        WHERE named_id=$1
+        ORDER BY id DESC
+        LIMIT $2
+    ) as kept
+)
+", named_id, *LOG_CAP)
+                .execute(pool)
+                .await?;
+        }
+        Kind::Private => {
+            let logged = sqlx::query!("
+INSERT INTO ecb.access_log (private_name, ip, success)
+SELECT p.name, $2, $3
+FROM ecb.private as p
+WHERE p.name=$1 AND p.owner_id IS NOT NULL
+", &access.clip, &access.ip, access.success)
+                .execute(pool)
+                .await?
+                .rows_affected();
+            if logged == 0 {
+                return Ok(());
+            }
+            sqlx::query!("
+DELETE FROM ecb.access_log
+WHERE private_name=$1 AND id < (
+    SELECT MIN(id) FROM (
+        SELECT id FROM ecb.access_log
+        WHERE private_name=$1
+        ORDER BY id DESC
+        LIMIT $2
+    ) as kept
+)
+", &access.clip, *LOG_CAP)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+struct Counters {
+    views: i64,
+    last_accessed: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+struct Entry {
+    ip: String,
+    success: bool,
+    at: chrono::DateTime<chrono::Utc>,
+}
+
+async fn counters(pool: &PgPool, kind: Kind, clip: &str) -> Result<Counters, Error> {
+    Ok(match kind {
+        Kind::Random => {
+            let code: i32 = clip.parse().unwrap_or_default();
+            sqlx::query_as!(
+                Counters,
+                "SELECT views, last_accessed FROM ecb.random WHERE id=$1",
+                code
+            )
+            .fetch_one(pool)
+            .await?
+        }
+        Kind::Named => {
+            let (ns, name) = namespace::split(clip);
+            sqlx::query_as!(Counters, "
+SELECT n.views, n.last_accessed
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE n.name=$1 AND a.name IS NOT DISTINCT FROM $2
+", name, ns)
+                .fetch_one(pool)
+                .await?
+        }
+        Kind::Private => {
+            sqlx::query_as!(
+                Counters,
+                "SELECT views, last_accessed FROM ecb.private WHERE name=$1",
+                clip
+            )
+            .fetch_one(pool)
+            .await?
+        }
+    })
+}
+
+/// View counters for everyone, plus the access log for the clip's owner
+pub async fn panel(pool: &PgPool, kind: Kind, clip: &str, owner: bool) -> Result<Markup, Error> {
+    let counters = counters(pool, kind, clip).await?;
+    let log = match (owner, kind) {
+        (true, Kind::Named) => {
+            let (ns, name) = namespace::split(clip);
+            sqlx::query_as!(Entry, "
+SELECT l.ip, l.success, l.at
+FROM ecb.access_log as l
+INNER JOIN ecb.named as n ON n.id=l.named_id
+INNER JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE n.name=$1 AND a.name=$2
+ORDER BY l.id DESC
+", name, ns)
+                .fetch_all(pool)
+                .await?
+        }
+        (true, Kind::Private) => {
+            sqlx::query_as!(Entry, "
+SELECT ip, success, at
+FROM ecb.access_log
+WHERE private_name=$1
+ORDER BY id DESC
+", clip)
+                .fetch_all(pool)
+                .await?
+        }
+        _ => vec![],
+    };
+    Ok(html! {
+        dl.clip-meta.clip-stats {
+            dt {"Views"} dd {(counters.views)}
+            @if let Some(last) = counters.last_accessed {
+                dt {"Last viewed"} dd {(last.format("%Y-%m-%d %H:%M UTC"))}
+            }
+        }
+        @if owner && !log.is_empty() {
+            details.access-log {
+                summary {"Access log"}
+                table {
+                    tr { th {"When"} th {"From"} th {"Result"} }
+                    @for entry in &log {
+                        tr {
+                            td {(entry.at.format("%Y-%m-%d %H:%M:%S UTC"))}
+                            td {(entry.ip)}
+                            td {
+                                @if entry.success { "viewed" } @else { "wrong password" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,394 @@
+//! Moving clips between instances as JSON Lines, one clip per line.
+//!
+//! Accounts are referred to by name since their ids differ between
+//! instances. Private clips travel as ciphertext, so their passwords keep
+//! working, but shares don't: the sealed password only opens on the
+//! instance that sealed it, so owners have to share them again.
+use super::*;
+use axum::{
+    body::Body,
+    http::header,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, TryStreamExt};
+use openssl::base64;
+use std::sync::LazyLock;
+use tokio::sync::mpsc;
+
+const CONTENT_TYPE: &str = "application/x-ndjson";
+pub static MAX_IMPORT: LazyLock<usize> =
+    LazyLock::new(|| limits::env_or("ECB_MAX_IMPORT", 64 * 1024 * 1024));
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Meta {
+    #[serde(default)]
+    format: Format,
+    language: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    views: i64,
+    last_accessed: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Record {
+    Random {
+        code: i32,
+        content: String,
+        #[serde(flatten)]
+        meta: Meta,
+    },
+    Named {
+        name: String,
+        namespace: Option<String>,
+        #[serde(default = "searchable")]
+        searchable: bool,
+        content: String,
+        #[serde(flatten)]
+        meta: Meta,
+    },
+    Private {
+        name: String,
+        owner: Option<String>,
+        /// base64 of the encrypted content
+        content: String,
+        #[serde(flatten)]
+        meta: Meta,
+    },
+}
+
+fn searchable() -> bool {
+    true
+}
+
+impl Record {
+    fn key(&self) -> String {
+        match self {
+            Record::Random { code, .. } => format!("random #{code}"),
+            Record::Named { name, namespace: Some(ns), .. } => format!("named {ns}/{name}"),
+            Record::Named { name, .. } => format!("named {name}"),
+            Record::Private { name, .. } => format!("private {name}"),
+        }
+    }
+}
+
+/// Every clip for admins, otherwise the account's namespace and private clips,
+/// streamed so large instances aren't held in memory
+pub async fn export(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, Error> {
+    let acc = accounts::get_acc(&cookies, &pool).await?;
+    let admin = accounts::is_admin(&cookies, &pool).await?;
+    let (lines, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        if let Err(e) = write_records(&pool, admin, acc.id, &lines).await {
+            // aborts the download instead of ending it as if it were complete
+            let _ = lines.send(Err(e)).await;
+        }
+    });
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (line, rx))
+    });
+    let filename = format!("attachment; filename=\"ecb-{}.jsonl\"", Utc::now().format("%Y%m%d"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, CONTENT_TYPE.to_owned()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        Body::from_stream(body),
+    ))
+}
+
+async fn send_record(lines: &mpsc::Sender<Result<String, Error>>, record: Record) -> Result<(), Error> {
+    let mut line = serde_json::to_string(&record)?;
+    line.push('\n');
+    lines
+        .send(Ok(line))
+        .await
+        .map_err(|_| IOError(std::io::ErrorKind::BrokenPipe.into()))
+}
+
+async fn write_records(
+    pool: &PgPool,
+    admin: bool,
+    account: Uuid,
+    lines: &mpsc::Sender<Result<String, Error>>,
+) -> Result<(), Error> {
+    if admin {
+        let mut rows = sqlx::query!("
+SELECT id, content, format, language, created_at, updated_at, views, last_accessed
+FROM ecb.random
+ORDER BY id
+")
+            .fetch(pool);
+        while let Some(r) = rows.try_next().await? {
+            send_record(lines, Record::Random {
+                code: r.id,
+                content: r.content,
+                meta: Meta {
+                    format: r.format.into(),
+                    language: r.language,
+                    created_at: r.created_at,
+                    updated_at: r.updated_at,
+                    views: r.views,
+                    last_accessed: r.last_accessed,
+                },
+            })
+            .await?;
+        }
+    }
+    let mut rows = sqlx::query!("
+SELECT
+    n.name, a.name as \"namespace?\", n.searchable, n.content,
+    n.format, n.language, n.created_at, n.updated_at, n.views, n.last_accessed
+FROM ecb.named as n
+LEFT JOIN inter.accounts as a ON a.id=n.namespace_id
+WHERE $1 OR n.namespace_id=$2
+ORDER BY n.id
+", admin, account)
+        .fetch(pool);
+    while let Some(r) = rows.try_next().await? {
+        send_record(lines, Record::Named {
+            name: r.name,
+            namespace: r.namespace,
+            searchable: r.searchable,
+            content: r.content,
+            meta: Meta {
+                format: r.format.into(),
+                language: r.language,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                views: r.views,
+                last_accessed: r.last_accessed,
+            },
+        })
+        .await?;
+    }
+    let mut rows = sqlx::query!("
+SELECT
+    p.name, a.name as \"owner?\", p.content,
+    p.format, p.language, p.created_at, p.updated_at, p.views, p.last_accessed
+FROM ecb.private as p
+LEFT JOIN inter.accounts as a ON a.id=p.owner_id
+WHERE $1 OR p.owner_id=$2
+ORDER BY p.name
+", admin, account)
+        .fetch(pool);
+    while let Some(r) = rows.try_next().await? {
+        send_record(lines, Record::Private {
+            name: r.name,
+            owner: r.owner,
+            content: base64::encode_block(&r.content),
+            meta: Meta {
+                format: r.format.into(),
+                language: r.language,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+                views: r.views,
+                last_accessed: r.last_accessed,
+            },
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ImportQuery {
+    /// replace existing clips instead of reporting them
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct Conflict {
+    line: usize,
+    clip: String,
+    reason: String,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct Report {
+    imported: usize,
+    conflicts: Vec<Conflict>,
+}
+
+async fn account_id(
+    tx: &mut sqlx::PgConnection,
+    name: &Option<String>,
+) -> Result<Result<Option<Uuid>, String>, Error> {
+    let Some(name) = name else {
+        return Ok(Ok(None));
+    };
+    Ok(sqlx::query!("SELECT id FROM inter.accounts WHERE name=$1", name)
+        .fetch_optional(tx)
+        .await?
+        .map(|r| Some(r.id))
+        .ok_or(format!("no account named \"{name}\"")))
+}
+
+/// Stores one record, `Ok(Err(reason))` when it conflicts with what's there;
+/// other errors only fail this record too
+async fn import_record(
+    tx: &mut sqlx::PgConnection,
+    record: Record,
+    acc: &accounts::Account,
+    admin: bool,
+    overwrite: bool,
+) -> Result<Result<(), String>, Error> {
+    let stored = match record {
+        Record::Random { code, content, meta } => {
+            if !admin {
+                return Ok(Err("only admins can import random clips".into()));
+            }
+            limits::check_size(limits::Kind::Random, &content)?;
+            moderation::check_blocked(&mut *tx, &content).await?;
+            let taken = sqlx::query!(
+                "SELECT id FROM ecb.random WHERE content=$1 AND id<>$2",
+                &content,
+                code
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            if let Some(taken) = taken {
+                return Ok(Err(format!("same content is already clip #{}", taken.id)));
+            }
+            sqlx::query!("
+INSERT INTO ecb.random (id, content, format, language, created_at, updated_at, views, last_accessed)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT (id) DO UPDATE SET
+    content=$2, format=$3, language=$4, created_at=$5, updated_at=$6, views=$7, last_accessed=$8
+WHERE $9
+", code, content, meta.format.as_str(), meta.language, meta.created_at, meta.updated_at,
+    meta.views, meta.last_accessed, overwrite)
+                .execute(&mut *tx)
+                .await?
+        }
+        Record::Named { name, namespace, searchable, content, meta } => {
+            if !admin && namespace.as_ref() != Some(&acc.name) {
+                return Ok(Err(format!("only admins can import outside namespace \"{}\"", acc.name)));
+            }
+            limits::check_size(limits::Kind::Named, &content)?;
+            moderation::check_blocked(&mut *tx, &content).await?;
+            let namespace_id = match account_id(&mut *tx, &namespace).await? {
+                Ok(id) => id,
+                Err(reason) => return Ok(Err(reason)),
+            };
+            let stored = match namespace_id {
+                None => sqlx::query!("
+INSERT INTO ecb.named (name, content, format, language, searchable, created_at, updated_at, views, last_accessed)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+ON CONFLICT (name) WHERE namespace_id IS NULL DO UPDATE SET
+    content=$2, format=$3, language=$4, searchable=$5,
+    created_at=$6, updated_at=$7, views=$8, last_accessed=$9
+WHERE $10 AND NOT ecb.named.hidden
+", name, content, meta.format.as_str(), meta.language, searchable, meta.created_at,
+    meta.updated_at, meta.views, meta.last_accessed, overwrite)
+                    .execute(&mut *tx)
+                    .await?,
+                Some(ns) => sqlx::query!("
+INSERT INTO ecb.named (namespace_id, name, content, format, language, searchable, created_at, updated_at, views, last_accessed)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+ON CONFLICT (namespace_id, name) WHERE namespace_id IS NOT NULL DO UPDATE SET
+    content=$3, format=$4, language=$5, searchable=$6,
+    created_at=$7, updated_at=$8, views=$9, last_accessed=$10
+WHERE $11 AND NOT ecb.named.hidden
+", ns, name, content, meta.format.as_str(), meta.language, searchable, meta.created_at,
+    meta.updated_at, meta.views, meta.last_accessed, overwrite)
+                    .execute(&mut *tx)
+                    .await?,
+            };
+            // with overwrite only a clip hidden by a moderator is left alone
+            if stored.rows_affected() == 0 && overwrite {
+                return Ok(Err("hidden by a moderator".into()));
+            }
+            stored
+        }
+        Record::Private { name, owner, content, meta } => {
+            if !admin && owner.as_ref() != Some(&acc.name) {
+                return Ok(Err("only admins can import clips owned by others".into()));
+            }
+            let owner_id = match account_id(&mut *tx, &owner).await? {
+                Ok(id) => id,
+                Err(reason) => return Ok(Err(reason)),
+            };
+            let Ok(content) = base64::decode_block(&content) else {
+                return Ok(Err("content isn't valid base64".into()));
+            };
+            // AES-CBC pads to the next whole block
+            let max = limits::LIMITS.max_private / 16 * 16 + 16;
+            if content.len() > max {
+                return Err(ContentTooLarge("private", limits::LIMITS.max_private));
+            }
+            // the share key was sealed by the other instance and can't be opened here
+            sqlx::query!("
+INSERT INTO ecb.private (name, owner_id, content, format, language, created_at, updated_at, views, last_accessed)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+ON CONFLICT (name) DO UPDATE SET
+    owner_id=$2, content=$3, format=$4, language=$5,
+    created_at=$6, updated_at=$7, views=$8, last_accessed=$9, shared_key=NULL
+WHERE $10 AND ($11 OR ecb.private.owner_id IS NOT DISTINCT FROM $2)
+", name, owner_id, content, meta.format.as_str(), meta.language, meta.created_at,
+    meta.updated_at, meta.views, meta.last_accessed, overwrite, admin)
+                .execute(&mut *tx)
+                .await?
+        }
+    };
+    if stored.rows_affected() == 0 {
+        let reason = if overwrite { "owned by another account" } else { "already exists" };
+        return Ok(Err(reason.into()));
+    }
+    Ok(Ok(()))
+}
+
+/// Imports an export, keeping names and codes even if this instance's
+/// name rules are stricter; conflicting or failing clips are left alone and
+/// reported, conflicts are overwritten with `?overwrite=true`
+pub async fn import(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    Query(params): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<Report>, Error> {
+    let acc = accounts::get_acc(&cookies, &pool).await?;
+    let admin = accounts::is_admin(&cookies, &pool).await?;
+    let mut report = Report::default();
+    let mut tx = pool.begin().await?;
+    for (i, line) in body.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_no = i + 1;
+        let record: Record = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                report.conflicts.push(Conflict {
+                    line: line_no,
+                    clip: String::new(),
+                    reason: format!("invalid record: {e}"),
+                });
+                continue;
+            }
+        };
+        let clip = record.key();
+        // a savepoint per record so one failing doesn't abort the rest
+        let mut record_tx = sqlx::Connection::begin(&mut *tx).await?;
+        let reason = match import_record(&mut record_tx, record, &acc, admin, params.overwrite).await {
+            Ok(Ok(())) => {
+                record_tx.commit().await?;
+                report.imported += 1;
+                continue;
+            }
+            Ok(Err(reason)) => reason,
+            Err(e) => e.message(),
+        };
+        record_tx.rollback().await?;
+        report.conflicts.push(Conflict { line: line_no, clip, reason });
+    }
+    tx.commit().await?;
+    Ok(Json(report))
+}
@@ -30,6 +30,17 @@ where P:AsRef<[u8]>
     out
 }
 
+/// Absolute address of this instance, for links that leave the site.
+/// `PUBLIC_URL` wins over the request's `Host` header.
+pub fn public_url(host: Option<&str>) -> String {
+    std::env::var("PUBLIC_URL")
+        .ok()
+        .or_else(|| host.map(|h| format!("http://{h}")))
+        .unwrap_or_else(|| "http://localhost:8000".to_owned())
+        .trim_end_matches('/')
+        .to_owned()
+}
+
 pub use sqlx::postgres::PgPool;
 pub async fn acquire_pool() -> Result<PgPool, Error> {
     dotenvy::dotenv()?;
@@ -45,6 +56,8 @@ pub enum Error {
     SqlxError(#[from] sqlx::Error),
     #[error(transparent)]
     IOError(#[from] std::io::Error),
+    #[error(transparent)]
+    AddrParseError(#[from] std::net::AddrParseError),
 }
 
 use maud::{html, Markup, Render, DOCTYPE};
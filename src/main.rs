@@ -8,6 +8,19 @@ use tower_http::services::ServeDir;
 async fn main() -> Result<(), Error> {
     let pool = acquire_pool().await?;
 
+    if let Err(e) = ecb::share::reseal_legacy(&pool).await {
+        eprintln!("ecb shares: can't seal old passwords again: {e}");
+    }
+
+    if let Ok(addr) = std::env::var("ECB_TCP_ADDR") {
+        let paste = ecb::paste::serve(pool.clone(), addr.parse()?);
+        tokio::spawn(async move {
+            if let Err(e) = paste.await {
+                eprintln!("ecb paste listener stopped: {e}");
+            }
+        });
+    }
+
     let app = Router::new()
         .route("/", get(index))
         .nest("/accounts", accounts::service())
@@ -20,7 +33,11 @@ async fn main() -> Result<(), Error> {
     let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 8000));
     let listener = tokio::net::TcpListener::bind(addr).await?;
     println!("listening in 0.0.0.0:8000");
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
     unreachable!()
 }
 
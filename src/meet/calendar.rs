@@ -0,0 +1,505 @@
+//! Times of calendar entries.
+//!
+//! Entries are stored as a UTC span plus the time zone they were planned
+//! in. Clients send wall-clock times (`2024-09-01T14:30` or just
+//! `2024-09-01` for all-day entries) that are read in the entry's zone,
+//! falling back to the account's default zone.
+//!
+//! Recurring entries carry an RFC 5545 RRULE that is expanded in their
+//! zone, so a weekly 10:00 meeting stays at 10:00 across DST changes.
+use super::user::Error::{self, *};
+use crate::*;
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use rrule::{RRule, RRuleSet, Unvalidated};
+
+/// Whose calendar an entry, feed or import belongs to
+#[derive(Debug, Clone, Copy)]
+pub enum Owner {
+    Account(Uuid),
+    Group(Uuid),
+}
+
+impl Owner {
+    pub fn ids(self) -> (Option<Uuid>, Option<Uuid>) {
+        match self {
+            Owner::Account(id) => (Some(id), None),
+            Owner::Group(id) => (None, Some(id)),
+        }
+    }
+}
+
+const DEFAULT_LENGTH: TimeDelta = TimeDelta::hours(1);
+
+/// Wall-clock time without a zone, a bare date means midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Local(pub NaiveDateTime);
+
+impl Local {
+    pub fn parse(s: &str) -> Option<Self> {
+        const FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"];
+        FORMATS
+            .iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .ok()
+                    .map(|d| d.and_time(NaiveTime::MIN))
+            })
+            .map(Local)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Local {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        Local::parse(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid date or time \"{s}\"")))
+    }
+}
+
+/// For optional times that forms send empty instead of leaving them out
+pub fn optional_local<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<Local>, D::Error> {
+    use serde::Deserialize;
+    match Option::<String>::deserialize(d)?.filter(|s| !s.trim().is_empty()) {
+        Some(s) => Local::parse(&s)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid date or time \"{s}\""))),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_zone(name: &str) -> Result<Tz, Error> {
+    name.parse()
+        .or(Err(InvalidEntry(format!("unknown time zone \"{name}\""))))
+}
+
+pub async fn account_zone(pool: &PgPool, account: Uuid) -> Result<String, Error> {
+    Ok(sqlx::query!("SELECT time_zone FROM inter.accounts WHERE id=$1", account)
+        .fetch_one(pool)
+        .await?
+        .time_zone)
+}
+
+pub fn to_utc(zone: Tz, local: NaiveDateTime) -> Result<DateTime<Utc>, Error> {
+    // a time skipped by a DST change doesn't exist, one repeated by it takes its first occurrence
+    zone.from_local_datetime(&local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or(InvalidEntry(format!("{local} doesn't exist in {zone}")))
+}
+
+pub fn to_local(zone: Tz, time: DateTime<Utc>) -> NaiveDateTime {
+    time.with_timezone(&zone).naive_local()
+}
+
+// dates near the ends of chrono's range would panic with `+`
+fn add(time: NaiveDateTime, delta: TimeDelta, what: &str) -> Result<NaiveDateTime, Error> {
+    time.checked_add_signed(delta)
+        .ok_or_else(|| InvalidEntry(format!("{what} out of range")))
+}
+
+/// Start, end and zone of an entry as stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub all_day: bool,
+    pub time_zone: String,
+}
+
+impl Span {
+    /// All-day entries cover whole days from the start date up to and
+    /// including the end date, timed ones default to an hour
+    pub fn new(
+        starts_at: Local,
+        ends_at: Option<Local>,
+        all_day: bool,
+        time_zone: &str,
+    ) -> Result<Self, Error> {
+        let zone = parse_zone(time_zone)?;
+        let (start, end) = if all_day {
+            let first = starts_at.0.date();
+            let last = ends_at.map(|e| e.0.date()).unwrap_or(first);
+            let after = last
+                .checked_add_days(Days::new(1))
+                .ok_or(InvalidEntry("end date out of range".to_owned()))?;
+            (first.and_time(NaiveTime::MIN), after.and_time(NaiveTime::MIN))
+        } else {
+            let end = match ends_at {
+                Some(end) => end.0,
+                None => add(starts_at.0, DEFAULT_LENGTH, "end time")?,
+            };
+            (starts_at.0, end)
+        };
+        let span = Span {
+            starts_at: to_utc(zone, start)?,
+            ends_at: to_utc(zone, end)?,
+            all_day,
+            time_zone: zone.name().to_owned(),
+        };
+        if span.ends_at <= span.starts_at {
+            return Err(InvalidEntry("entries must end after they start".to_owned()));
+        }
+        Ok(span)
+    }
+
+    /// Applies the changed fields; unchanged times keep their wall-clock
+    /// time, so moving an entry to another zone keeps it at "10:00"
+    pub fn update(
+        &self,
+        starts_at: Option<Local>,
+        ends_at: Option<Local>,
+        all_day: Option<bool>,
+        time_zone: Option<&str>,
+    ) -> Result<Self, Error> {
+        let zone = parse_zone(&self.time_zone)?;
+        let old_start = Local(to_local(zone, self.starts_at));
+        let mut old_end = to_local(zone, self.ends_at);
+        if self.all_day {
+            // stored exclusive, given inclusive
+            old_end = add(old_end, -TimeDelta::days(1), "end date")?;
+        }
+        let all_day = all_day.unwrap_or(self.all_day);
+        let starts_at = starts_at.unwrap_or(old_start);
+        let ends_at = match ends_at {
+            Some(end) => Some(end),
+            // the old end means nothing once an entry switches between all-day and timed
+            None if all_day != self.all_day => None,
+            // a moved start moves the whole entry
+            None => Some(Local(add(old_end, starts_at.0 - old_start.0, "end time")?)),
+        };
+        Span::new(starts_at, ends_at, all_day, time_zone.unwrap_or(&self.time_zone))
+    }
+
+    /// Moves a series by as much as `edited` differs from `seen`, one of its
+    /// occurrences, so times entered while looking at it apply to all
+    pub fn shift(&self, seen: &Span, edited: &Span) -> Result<Self, Error> {
+        let zone = parse_zone(&self.time_zone)?;
+        let edited_zone = parse_zone(&edited.time_zone)?;
+        let moved = to_local(edited_zone, edited.starts_at) - to_local(zone, seen.starts_at);
+        let length = to_local(edited_zone, edited.ends_at) - to_local(edited_zone, edited.starts_at);
+        let start = add(to_local(zone, self.starts_at), moved, "start time")?;
+        let end = match edited.all_day {
+            true => add(start, length - TimeDelta::days(1), "end date")?,
+            false => add(start, length, "end time")?,
+        };
+        Span::new(Local(start), Some(Local(end)), edited.all_day, &edited.time_zone)
+    }
+
+    /// Where the occurrence starting at `start` goes once the series moves
+    /// from this span to `moved`, by the same wall-clock offset
+    pub fn carry(&self, moved: &Span, start: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+        let zone = parse_zone(&self.time_zone)?;
+        let moved_zone = parse_zone(&moved.time_zone)?;
+        let offset = to_local(moved_zone, moved.starts_at) - to_local(zone, self.starts_at);
+        to_utc(moved_zone, add(to_local(zone, start), offset, "occurrence")?)
+    }
+
+    /// The span of the occurrence starting at `start`, as long in
+    /// wall-clock time as this one so all-day entries stay whole days
+    pub fn at(&self, start: DateTime<Utc>) -> Result<Self, Error> {
+        let zone = parse_zone(&self.time_zone)?;
+        let length = to_local(zone, self.ends_at) - to_local(zone, self.starts_at);
+        let local = to_local(zone, start);
+        Ok(Span {
+            starts_at: start,
+            ends_at: to_utc(zone, add(local, length, "end time")?)?,
+            all_day: self.all_day,
+            time_zone: self.time_zone.clone(),
+        })
+    }
+}
+
+// expanding is cut off here, so a rule without COUNT or UNTIL stays cheap
+const MAX_OCCURRENCES: u16 = 1000;
+
+fn rule_set(span: &Span, rule: &str, exdates: &[DateTime<Utc>]) -> Result<RRuleSet, Error> {
+    let zone = rrule::Tz::Tz(parse_zone(&span.time_zone)?);
+    let invalid = |e: rrule::RRuleError| InvalidEntry(format!("invalid recurrence rule: {e}"));
+    let rule: RRule<Unvalidated> = rule.parse().map_err(invalid)?;
+    Ok(rule
+        .build(span.starts_at.with_timezone(&zone))
+        .map_err(invalid)?
+        .set_exdates(exdates.iter().map(|d| d.with_timezone(&zone)).collect()))
+}
+
+/// Normalizes `rule`, failing if it can't recur from the span's start
+pub fn check_rule(span: &Span, rule: &str) -> Result<String, Error> {
+    let rule = rule.trim();
+    let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+    rule_set(span, rule, &[])?;
+    Ok(rule.to_owned())
+}
+
+/// Starts of the occurrences overlapping `from..to`
+pub fn occurrences(
+    span: &Span,
+    rule: &str,
+    exdates: &[DateTime<Utc>],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>, Error> {
+    let zone = rrule::Tz::Tz(parse_zone(&span.time_zone)?);
+    let length = span.ends_at - span.starts_at;
+    let starts = rule_set(span, rule, exdates)?
+        .after((from - length).with_timezone(&zone))
+        .before(to.with_timezone(&zone))
+        .all(MAX_OCCURRENCES)
+        .dates;
+    let mut overlapping = vec![];
+    for start in starts {
+        let occurrence = span.at(start.with_timezone(&Utc))?;
+        if occurrence.ends_at > from && occurrence.starts_at < to {
+            overlapping.push(occurrence.starts_at);
+        }
+    }
+    Ok(overlapping)
+}
+
+pub fn is_occurrence(
+    span: &Span,
+    rule: &str,
+    exdates: &[DateTime<Utc>],
+    start: DateTime<Utc>,
+) -> Result<bool, Error> {
+    let zone = rrule::Tz::Tz(parse_zone(&span.time_zone)?);
+    let start = start.with_timezone(&zone);
+    Ok(!rule_set(span, rule, exdates)?
+        .after(start)
+        .before(start)
+        .all(1)
+        .dates
+        .is_empty())
+}
+
+fn without_end(rule: &str) -> impl Iterator<Item = &str> {
+    rule.split(';')
+        .filter(|part| !part.starts_with("COUNT=") && !part.starts_with("UNTIL="))
+}
+
+/// `rule` stopped right before `start`
+pub fn truncate(rule: &str, start: DateTime<Utc>) -> String {
+    let until = (start - TimeDelta::seconds(1)).format("%Y%m%dT%H%M%SZ");
+    without_end(rule)
+        .chain([format!("UNTIL={until}").as_str()])
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// What's left of `rule` from the occurrence at `start` on
+pub fn remainder(span: &Span, rule: &str, start: DateTime<Utc>) -> Result<String, Error> {
+    let count = rule
+        .split(';')
+        .find_map(|part| part.strip_prefix("COUNT="))
+        .and_then(|count| count.parse::<usize>().ok());
+    let Some(count) = count else {
+        // UNTIL, or no end at all, is the same for the rest of the series
+        return Ok(rule.to_owned());
+    };
+    let zone = rrule::Tz::Tz(parse_zone(&span.time_zone)?);
+    let before = rule_set(span, rule, &[])?
+        .before((start - TimeDelta::seconds(1)).with_timezone(&zone))
+        .all(MAX_OCCURRENCES)
+        .dates
+        .len();
+    Ok(without_end(rule)
+        .chain([format!("COUNT={}", count.saturating_sub(before).max(1)).as_str()])
+        .collect::<Vec<_>>()
+        .join(";"))
+}
+
+/// `from..to` of a range query, as RFC 3339 times
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+pub struct Range {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Range {
+    pub fn check(self) -> Result<Self, Error> {
+        if self.to <= self.from {
+            return Err(InvalidEntry("the range must end after it starts".to_owned()));
+        }
+        Ok(self)
+    }
+}
+
+/// An entry of either calendar, `group_id` is unset for personal ones
+struct Stored {
+    id: i32,
+    group_id: Option<Uuid>,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    all_day: bool,
+    time_zone: String,
+    title: String,
+    description: Option<String>,
+    rrule: Option<String>,
+    exdates: Vec<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct Occurrence {
+    pub id: i32,
+    pub group_id: Option<Uuid>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub all_day: bool,
+    pub time_zone: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub rrule: Option<String>,
+    // pass back as `?occurrence=` to edit only this one
+    pub occurrence: Option<DateTime<Utc>>,
+}
+
+/// Entries of `account`'s and `groups`' calendars overlapping `range` in
+/// chronological order, recurring ones expanded into their occurrences
+pub async fn in_range(
+    pool: &PgPool,
+    account: Option<Uuid>,
+    groups: &[Uuid],
+    range: Range,
+) -> Result<Vec<Occurrence>, Error> {
+    let entries = sqlx::query_as!(Stored, r#"
+SELECT
+    id as "id!", NULL::uuid as group_id, starts_at as "starts_at!", ends_at as "ends_at!",
+    all_day as "all_day!", time_zone as "time_zone!", title as "title!", description,
+    rrule, exdates as "exdates!"
+FROM meet.user_calendar_entries
+WHERE owner_id=$1 AND starts_at < $3 AND (rrule IS NOT NULL OR ends_at > $2)
+UNION ALL
+SELECT
+    id, group_id, starts_at, ends_at, all_day, time_zone, title, description, rrule, exdates
+FROM meet.group_calendar_entries
+WHERE group_id=ANY($4) AND starts_at < $3 AND (rrule IS NOT NULL OR ends_at > $2)
+"#, account, range.from, range.to, groups)
+        .fetch_all(pool)
+        .await?;
+    let mut occurrences = vec![];
+    for entry in entries {
+        let span = Span {
+            starts_at: entry.starts_at,
+            ends_at: entry.ends_at,
+            all_day: entry.all_day,
+            time_zone: entry.time_zone,
+        };
+        let starts = match &entry.rrule {
+            Some(rule) => occurrences_of(&span, rule, &entry.exdates, range)?,
+            None => vec![None],
+        };
+        for start in starts {
+            let span = match start {
+                Some(start) => span.at(start)?,
+                None => span.clone(),
+            };
+            occurrences.push(Occurrence {
+                id: entry.id,
+                group_id: entry.group_id,
+                starts_at: span.starts_at,
+                ends_at: span.ends_at,
+                all_day: span.all_day,
+                time_zone: span.time_zone,
+                title: entry.title.clone(),
+                description: entry.description.clone(),
+                rrule: entry.rrule.clone(),
+                occurrence: start,
+            });
+        }
+    }
+    occurrences.sort_by_key(|o| o.starts_at);
+    Ok(occurrences)
+}
+
+fn occurrences_of(
+    span: &Span,
+    rule: &str,
+    exdates: &[DateTime<Utc>],
+    range: Range,
+) -> Result<Vec<Option<DateTime<Utc>>>, Error> {
+    Ok(occurrences(span, rule, exdates, range.from, range.to)?
+        .into_iter()
+        .map(Some)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: &str, end: Option<&str>, all_day: bool) -> Span {
+        let local = |s| Local::parse(s).unwrap();
+        Span::new(local(start), end.map(local), all_day, "Europe/Berlin").unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn all(span: &Span, rule: &str) -> Vec<DateTime<Utc>> {
+        occurrences(span, rule, &[], utc("2026-01-01T00:00:00Z"), utc("2027-01-01T00:00:00Z")).unwrap()
+    }
+
+    #[test]
+    fn count_carries_over_a_split() {
+        let series = span("2026-03-26T10:00", None, false);
+        let rule = "FREQ=DAILY;COUNT=6";
+        let before = all(&series, rule);
+        assert_eq!(before.len(), 6);
+        // the fourth occurrence is the first after the change to summer time
+        let at = before[3];
+        assert_eq!(at, utc("2026-03-29T08:00:00Z"));
+
+        let head = all(&series, &truncate(rule, at));
+        let rest = remainder(&series, rule, at).unwrap();
+        assert_eq!(rest, "FREQ=DAILY;COUNT=3");
+        let tail = all(&series.at(at).unwrap(), &rest);
+        assert_eq!([head, tail].concat(), before);
+    }
+
+    #[test]
+    fn remainder_keeps_one_occurrence_past_the_count() {
+        let series = span("2026-03-26T10:00", None, false);
+        let rest = remainder(&series, "FREQ=DAILY;COUNT=2", utc("2026-04-01T08:00:00Z")).unwrap();
+        assert_eq!(rest, "FREQ=DAILY;COUNT=1");
+        assert_eq!(remainder(&series, "FREQ=WEEKLY", utc("2026-04-02T08:00:00Z")).unwrap(), "FREQ=WEEKLY");
+    }
+
+    #[test]
+    fn truncate_stops_right_before_the_split_across_dst() {
+        // 02:30 comes twice on the night back to winter time
+        let series = span("2026-10-23T02:30", None, false);
+        let rule = "FREQ=DAILY;COUNT=5";
+        let before = all(&series, rule);
+        let at = before[2];
+        assert_eq!(at, utc("2026-10-25T00:30:00Z"));
+        let rule = truncate(rule, at);
+        assert_eq!(rule, "FREQ=DAILY;UNTIL=20261025T002959Z");
+        assert_eq!(all(&series, &rule), before[..2]);
+        // the same split in spring, where the days get shorter by an hour
+        let series = span("2026-03-27T10:00", None, false);
+        let at = utc("2026-03-29T08:00:00Z");
+        assert_eq!(all(&series, &truncate("FREQ=DAILY", at)), [
+            utc("2026-03-27T09:00:00Z"),
+            utc("2026-03-28T09:00:00Z"),
+        ]);
+    }
+
+    #[test]
+    fn all_day_occurrences_stay_whole_days() {
+        let day = span("2026-03-28", Some("2026-03-28"), true);
+        assert_eq!(day.ends_at - day.starts_at, TimeDelta::hours(24));
+        let sunday = day.at(utc("2026-03-28T23:00:00Z")).unwrap();
+        assert_eq!(sunday.ends_at, utc("2026-03-29T22:00:00Z"));
+    }
+
+    #[test]
+    fn carry_keeps_the_wall_clock_offset() {
+        let from = span("2026-03-27T10:00", None, false);
+        let to = span("2026-03-27T11:30", None, false);
+        assert_eq!(from.carry(&to, utc("2026-03-30T08:00:00Z")).unwrap(), utc("2026-03-30T09:30:00Z"));
+        // 02:30 is skipped on the day the clocks go forward
+        let early = span("2026-03-27T02:30", None, false);
+        assert!(from.carry(&early, utc("2026-03-29T08:00:00Z")).is_err());
+    }
+}
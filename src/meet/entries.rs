@@ -0,0 +1,537 @@
+//! Entries of either calendar, shared by the personal and group endpoints.
+//!
+//! Both tables have the same columns apart from who they belong to, so
+//! every query here comes in two flavours picked by `Owner`.
+use super::calendar::{self, Local, Owner, Span};
+use super::user::Error::{self, *};
+use crate::*;
+use chrono::{DateTime, Utc};
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct Entry {
+    pub id: i32,
+    pub starts_at: DateTime<Utc>,
+    // exclusive, the midnight after the last day for all-day entries
+    pub ends_at: DateTime<Utc>,
+    pub all_day: bool,
+    pub time_zone: String,
+    pub title: String,
+    pub description: Option<String>,
+    // RFC 5545 RRULE value of recurring entries
+    pub rrule: Option<String>,
+    // starts of the occurrences that were deleted or edited on their own
+    pub exdates: Vec<DateTime<Utc>>,
+    // series and start of an occurrence that was edited on its own
+    pub parent_id: Option<i32>,
+    pub recurrence_at: Option<DateTime<Utc>>,
+}
+
+impl Entry {
+    fn span(&self) -> Span {
+        Span {
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            all_day: self.all_day,
+            time_zone: self.time_zone.clone(),
+        }
+    }
+
+    fn with_span(self, span: Span) -> Self {
+        Self {
+            starts_at: span.starts_at,
+            ends_at: span.ends_at,
+            all_day: span.all_day,
+            time_zone: span.time_zone,
+            ..self
+        }
+    }
+}
+
+// times are wall-clock times in `time_zone`, see `calendar::Local`
+#[derive(serde::Deserialize, Debug)]
+pub struct Creator {
+    starts_at: Local,
+    // inclusive last day for all-day entries, an hour after the start when missing
+    #[serde(default, deserialize_with = "calendar::optional_local")]
+    ends_at: Option<Local>,
+    #[serde(default)]
+    all_day: bool,
+    // the creating account's time zone when missing
+    time_zone: Option<String>,
+    title: String,
+    description: Option<String>,
+    rrule: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Updator {
+    #[serde(default, deserialize_with = "calendar::optional_local")]
+    starts_at: Option<Local>,
+    #[serde(default, deserialize_with = "calendar::optional_local")]
+    ends_at: Option<Local>,
+    all_day: Option<bool>,
+    time_zone: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    // an empty rule stops the entry from recurring
+    rrule: Option<String>,
+}
+
+impl Updator {
+    /// Whether the update leaves `rule` as it is, forms always send it
+    fn keeps_rule(&self, rule: &str) -> bool {
+        match &self.rrule {
+            None => true,
+            Some(new) => new.trim().trim_start_matches("RRULE:") == rule,
+        }
+    }
+
+    fn apply(&self, base: Entry) -> Result<Entry, Error> {
+        let span = base.span().update(
+            self.starts_at,
+            self.ends_at,
+            self.all_day,
+            self.time_zone.as_deref().filter(|z| !z.is_empty()),
+        )?;
+        let rrule = match &self.rrule {
+            None => base.rrule.clone(),
+            Some(rule) if rule.trim().is_empty() => None,
+            Some(rule) => Some(calendar::check_rule(&span, rule)?),
+        };
+        Ok(Entry {
+            title: self.title.clone().unwrap_or(base.title.clone()),
+            description: self.description.clone().or(base.description.clone()),
+            rrule,
+            ..base.with_span(span)
+        })
+    }
+}
+
+/// Which occurrences of a recurring entry an edit or delete applies to
+#[derive(serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    This,
+    Following,
+    #[default]
+    All,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct EditScope {
+    #[serde(default)]
+    scope: Scope,
+    // start of the occurrence, as listed by `GET .../calendar`
+    occurrence: Option<DateTime<Utc>>,
+}
+
+impl EditScope {
+    fn occurrence(&self, entry: &Entry) -> Result<Option<DateTime<Utc>>, Error> {
+        // all of a series may still be edited from one of its occurrences
+        if self.scope == Scope::All && (entry.rrule.is_none() || self.occurrence.is_none()) {
+            return Ok(None);
+        }
+        let (Some(rule), Some(occurrence)) = (&entry.rrule, self.occurrence) else {
+            return Err(InvalidEntry(
+                "editing occurrences needs a recurring entry and an occurrence".to_owned(),
+            ));
+        };
+        if !calendar::is_occurrence(&entry.span(), rule, &entry.exdates, occurrence)? {
+            return Err(InvalidEntry(format!(
+                "{occurrence} isn't an occurrence of entry {}",
+                entry.id
+            )));
+        }
+        Ok(Some(occurrence))
+    }
+}
+
+pub async fn fetch(pool: &PgPool, owner: Owner, entry_id: i32) -> Result<Entry, Error> {
+    Ok(match owner {
+        Owner::Account(id) => sqlx::query_as!(Entry, "
+SELECT
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+FROM meet.user_calendar_entries
+WHERE owner_id=$1 AND id=$2
+", id, entry_id)
+            .fetch_one(pool)
+            .await?,
+        Owner::Group(id) => sqlx::query_as!(Entry, "
+SELECT
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+FROM meet.group_calendar_entries
+WHERE group_id=$1 AND id=$2
+", id, entry_id)
+            .fetch_one(pool)
+            .await?,
+    })
+}
+
+/// `fetch` inside a transaction, keeping others off the entry until it ends
+async fn lock(conn: &mut sqlx::PgConnection, owner: Owner, entry_id: i32) -> Result<Entry, Error> {
+    Ok(match owner {
+        Owner::Account(id) => sqlx::query_as!(Entry, "
+SELECT
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+FROM meet.user_calendar_entries
+WHERE owner_id=$1 AND id=$2
+FOR UPDATE
+", id, entry_id)
+            .fetch_one(conn)
+            .await?,
+        Owner::Group(id) => sqlx::query_as!(Entry, "
+SELECT
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+FROM meet.group_calendar_entries
+WHERE group_id=$1 AND id=$2
+FOR UPDATE
+", id, entry_id)
+            .fetch_one(conn)
+            .await?,
+    })
+}
+
+async fn insert(conn: &mut sqlx::PgConnection, owner: Owner, entry: &Entry) -> Result<Entry, Error> {
+    Ok(match owner {
+        Owner::Account(id) => sqlx::query_as!(Entry, "
+INSERT INTO meet.user_calendar_entries
+(starts_at, ends_at, all_day, time_zone, title, description,
+ rrule, exdates, parent_id, recurrence_at, owner_id)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+RETURNING
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+", entry.starts_at, entry.ends_at, entry.all_day, entry.time_zone, entry.title,
+    entry.description, entry.rrule, &entry.exdates, entry.parent_id, entry.recurrence_at, id)
+            .fetch_one(conn)
+            .await?,
+        Owner::Group(id) => sqlx::query_as!(Entry, "
+INSERT INTO meet.group_calendar_entries
+(starts_at, ends_at, all_day, time_zone, title, description,
+ rrule, exdates, parent_id, recurrence_at, group_id)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+RETURNING
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+", entry.starts_at, entry.ends_at, entry.all_day, entry.time_zone, entry.title,
+    entry.description, entry.rrule, &entry.exdates, entry.parent_id, entry.recurrence_at, id)
+            .fetch_one(conn)
+            .await?,
+    })
+}
+
+async fn store(conn: &mut sqlx::PgConnection, owner: Owner, entry: &Entry) -> Result<Entry, Error> {
+    Ok(match owner {
+        Owner::Account(id) => sqlx::query_as!(Entry, "
+UPDATE meet.user_calendar_entries
+SET
+    starts_at=$1,
+    ends_at=$2,
+    all_day=$3,
+    time_zone=$4,
+    title=$5,
+    description=$6,
+    rrule=$7,
+    exdates=$8
+WHERE id=$9 AND owner_id=$10
+RETURNING
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+", entry.starts_at, entry.ends_at, entry.all_day, entry.time_zone, entry.title,
+    entry.description, entry.rrule, &entry.exdates, entry.id, id)
+            .fetch_one(conn)
+            .await?,
+        Owner::Group(id) => sqlx::query_as!(Entry, "
+UPDATE meet.group_calendar_entries
+SET
+    starts_at=$1,
+    ends_at=$2,
+    all_day=$3,
+    time_zone=$4,
+    title=$5,
+    description=$6,
+    rrule=$7,
+    exdates=$8
+WHERE id=$9 AND group_id=$10
+RETURNING
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+", entry.starts_at, entry.ends_at, entry.all_day, entry.time_zone, entry.title,
+    entry.description, entry.rrule, &entry.exdates, entry.id, id)
+            .fetch_one(conn)
+            .await?,
+    })
+}
+
+/// Hands the series' occurrences edited on their own from `from` on to
+/// `following`, deleting them when there is none
+async fn reparent(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    series: i32,
+    from: DateTime<Utc>,
+    following: Option<i32>,
+) -> Result<(), Error> {
+    match (owner, following) {
+        (Owner::Account(_), Some(_)) => sqlx::query!("
+UPDATE meet.user_calendar_entries
+SET parent_id=$3
+WHERE parent_id=$1 AND recurrence_at >= $2
+", series, from, following)
+            .execute(conn)
+            .await?,
+        (Owner::Group(_), Some(_)) => sqlx::query!("
+UPDATE meet.group_calendar_entries
+SET parent_id=$3
+WHERE parent_id=$1 AND recurrence_at >= $2
+", series, from, following)
+            .execute(conn)
+            .await?,
+        (Owner::Account(_), None) => sqlx::query!("
+DELETE FROM meet.user_calendar_entries
+WHERE parent_id=$1 AND recurrence_at >= $2
+", series, from)
+            .execute(conn)
+            .await?,
+        (Owner::Group(_), None) => sqlx::query!("
+DELETE FROM meet.group_calendar_entries
+WHERE parent_id=$1 AND recurrence_at >= $2
+", series, from)
+            .execute(conn)
+            .await?,
+    };
+    Ok(())
+}
+
+/// Moves the exdates and the occurrences edited on their own of a series
+/// that moved from `from` along with it, so they keep standing in for the
+/// same occurrences
+async fn carry_exceptions(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    from: &Span,
+    series: &mut Entry,
+) -> Result<(), Error> {
+    let to = series.span();
+    if to.starts_at == from.starts_at && to.time_zone == from.time_zone {
+        return Ok(());
+    }
+    series.exdates = series
+        .exdates
+        .iter()
+        .map(|d| from.carry(&to, *d))
+        .collect::<Result<_, _>>()?;
+    let children: Vec<(i32, DateTime<Utc>)> = match owner {
+        Owner::Account(_) => sqlx::query!(r#"
+SELECT id, recurrence_at as "recurrence_at!"
+FROM meet.user_calendar_entries
+WHERE parent_id=$1 AND recurrence_at IS NOT NULL
+FOR UPDATE
+"#, series.id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|c| (c.id, c.recurrence_at))
+            .collect(),
+        Owner::Group(_) => sqlx::query!(r#"
+SELECT id, recurrence_at as "recurrence_at!"
+FROM meet.group_calendar_entries
+WHERE parent_id=$1 AND recurrence_at IS NOT NULL
+FOR UPDATE
+"#, series.id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|c| (c.id, c.recurrence_at))
+            .collect(),
+    };
+    let ids: Vec<i32> = children.iter().map(|c| c.0).collect();
+    let moved = children
+        .iter()
+        .map(|c| from.carry(&to, c.1))
+        .collect::<Result<Vec<_>, _>>()?;
+    match owner {
+        Owner::Account(_) => sqlx::query!("
+UPDATE meet.user_calendar_entries AS e
+SET recurrence_at=moved.recurrence_at
+FROM UNNEST($1::int[], $2::timestamptz[]) AS moved(id, recurrence_at)
+WHERE e.id=moved.id
+", &ids, &moved)
+            .execute(conn)
+            .await?,
+        Owner::Group(_) => sqlx::query!("
+UPDATE meet.group_calendar_entries AS e
+SET recurrence_at=moved.recurrence_at
+FROM UNNEST($1::int[], $2::timestamptz[]) AS moved(id, recurrence_at)
+WHERE e.id=moved.id
+", &ids, &moved)
+            .execute(conn)
+            .await?,
+    };
+    Ok(())
+}
+
+async fn remove(conn: &mut sqlx::PgConnection, owner: Owner, entry_id: i32) -> Result<Entry, Error> {
+    Ok(match owner {
+        Owner::Account(id) => sqlx::query_as!(Entry, "
+DELETE FROM meet.user_calendar_entries
+WHERE id=$1 AND owner_id=$2
+RETURNING
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+", entry_id, id)
+            .fetch_one(conn)
+            .await?,
+        Owner::Group(id) => sqlx::query_as!(Entry, "
+DELETE FROM meet.group_calendar_entries
+WHERE id=$1 AND group_id=$2
+RETURNING
+    id, starts_at, ends_at, all_day, time_zone, title, description,
+    rrule, exdates, parent_id, recurrence_at
+", entry_id, id)
+            .fetch_one(conn)
+            .await?,
+    })
+}
+
+/// Ends the series right before `occurrence`, handing it what follows
+async fn split_series(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    series: &Entry,
+    occurrence: DateTime<Utc>,
+    following: Option<i32>,
+) -> Result<Entry, Error> {
+    let rule = series.rrule.as_deref().unwrap_or_default();
+    let truncated = Entry {
+        rrule: Some(calendar::truncate(rule, occurrence)),
+        exdates: series.exdates.iter().copied().filter(|d| *d < occurrence).collect(),
+        ..series.clone()
+    };
+    reparent(&mut *conn, owner, series.id, occurrence, following).await?;
+    store(conn, owner, &truncated).await
+}
+
+/// Creates an entry, its times read in `default_zone` unless it names one
+pub async fn create(
+    pool: &PgPool,
+    owner: Owner,
+    default_zone: &str,
+    entry: Creator,
+) -> Result<Entry, Error> {
+    let time_zone = entry.time_zone.filter(|z| !z.is_empty());
+    let span = Span::new(
+        entry.starts_at,
+        entry.ends_at,
+        entry.all_day,
+        time_zone.as_deref().unwrap_or(default_zone),
+    )?;
+    let rrule = match entry.rrule.filter(|rule| !rule.trim().is_empty()) {
+        Some(rule) => Some(calendar::check_rule(&span, &rule)?),
+        None => None,
+    };
+    let created = Entry {
+        id: 0,
+        title: entry.title,
+        description: entry.description.filter(|d| !d.is_empty()),
+        rrule,
+        exdates: vec![],
+        parent_id: None,
+        recurrence_at: None,
+        starts_at: span.starts_at,
+        ends_at: span.ends_at,
+        all_day: span.all_day,
+        time_zone: span.time_zone,
+    };
+    let mut conn = pool.acquire().await?;
+    insert(&mut conn, owner, &created).await
+}
+
+pub async fn delete(
+    pool: &PgPool,
+    owner: Owner,
+    entry_id: i32,
+    scope: EditScope,
+) -> Result<Entry, Error> {
+    let mut tx = pool.begin().await?;
+    let current = lock(&mut tx, owner, entry_id).await?;
+    let deleted = match scope.occurrence(&current)? {
+        Some(occurrence) if scope.scope == Scope::This => {
+            let mut series = current.clone();
+            series.exdates.push(occurrence);
+            store(&mut tx, owner, &series).await?
+        }
+        Some(occurrence) if scope.scope == Scope::Following && occurrence > current.starts_at => {
+            split_series(&mut tx, owner, &current, occurrence, None).await?
+        }
+        // every occurrence from the first one on is the whole series
+        _ => remove(&mut tx, owner, entry_id).await?,
+    };
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+pub async fn update(
+    pool: &PgPool,
+    owner: Owner,
+    entry_id: i32,
+    scope: EditScope,
+    entry: Updator,
+) -> Result<Entry, Error> {
+    let mut tx = pool.begin().await?;
+    let current = lock(&mut tx, owner, entry_id).await?;
+    let updated = match scope.occurrence(&current)? {
+        Some(occurrence) if scope.scope == Scope::This => {
+            let single = Entry {
+                exdates: vec![],
+                parent_id: Some(current.id),
+                recurrence_at: Some(occurrence),
+                ..current.clone().with_span(current.span().at(occurrence)?)
+            };
+            // forms send the series' rule along, a single occurrence never recurs
+            let single = Entry { rrule: None, ..entry.apply(single)? };
+            let mut series = current.clone();
+            series.exdates.push(occurrence);
+            store(&mut tx, owner, &series).await?;
+            insert(&mut tx, owner, &single).await?
+        }
+        Some(occurrence) if scope.scope == Scope::Following && occurrence > current.starts_at => {
+            let rule = current.rrule.as_deref().unwrap_or_default();
+            let remainder = calendar::remainder(&current.span(), rule, occurrence)?;
+            let seen = current.span().at(occurrence)?;
+            let rest = Entry {
+                rrule: Some(remainder.clone()),
+                exdates: current.exdates.iter().copied().filter(|d| *d >= occurrence).collect(),
+                ..current.clone().with_span(seen.clone())
+            };
+            let mut rest = entry.apply(rest)?;
+            if entry.keeps_rule(rule) {
+                rest.rrule = Some(remainder);
+            }
+            let mut following = insert(&mut tx, owner, &rest).await?;
+            split_series(&mut tx, owner, &current, occurrence, Some(following.id)).await?;
+            carry_exceptions(&mut tx, owner, &seen, &mut following).await?;
+            store(&mut tx, owner, &following).await?
+        }
+        Some(occurrence) if scope.scope == Scope::All => {
+            let seen = current.span().at(occurrence)?;
+            let edited = entry.apply(current.clone().with_span(seen.clone()))?;
+            let span = current.span().shift(&seen, &edited.span())?;
+            let mut series = edited.with_span(span);
+            carry_exceptions(&mut tx, owner, &current.span(), &mut series).await?;
+            store(&mut tx, owner, &series).await?
+        }
+        // every occurrence from the first one on is the whole series
+        _ => {
+            let mut series = entry.apply(current.clone())?;
+            carry_exceptions(&mut tx, owner, &current.span(), &mut series).await?;
+            store(&mut tx, owner, &series).await?
+        }
+    };
+    tx.commit().await?;
+    Ok(updated)
+}
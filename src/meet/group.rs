@@ -1,16 +1,637 @@
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error(transparent)]
+    AccountError(#[from] crate::accounts::Error),
+    #[error(transparent)]
+    SqlxError(#[from] sqlx::Error),
+    #[error("Authorization Error: {0}")]
+    AuthError(String),
+    #[error("Business logic Error: {0}")]
+    LogicError(String),
+    #[error("No usable invite link with this token")]
+    UnknownLink,
+    #[error(transparent)]
+    UserError(#[from] user::Error),
+}
+use self::Error::*;
+use axum::http::StatusCode;
+
+//TODO SqlxError actually handle different db errors
+impl DescribeError for Error {
+    fn describe(&self) -> (StatusCode, String) {
+        // for special handling of errors
+        let code = match self {
+            AccountError(e) => return e.describe(),
+            UserError(e) => return e.describe(),
+            SqlxError(_) => StatusCode::BAD_REQUEST,
+            AuthError(_) => StatusCode::FORBIDDEN,
+            LogicError(_) => StatusCode::FORBIDDEN,
+            UnknownLink => StatusCode::NOT_FOUND,
+        };
+        ( code, format!("{self:?}") )
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        (self.code(), self.message()).into_response()
+    }
 }
 
 use crate::*;
 use axum::{
-//    extract::*,
-//    response::IntoResponse,
-//    routing::{get, post},
+    extract::*,
+    response::{IntoResponse, Json as JSON, Redirect, Response},
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use tower_cookies::Cookies;
+use super::calendar::{self, Owner};
+use super::{entries, ical};
+use chrono::{DateTime, Utc};
+use super::user::{self, GetNoteEntries, Paginate};
+use super::views::{Payload, Submitted};
 
 pub fn service() -> Router<PgPool> {
     Router::new()
+        .route("/:id/calendar", get(get_calendar_entries))
+        .route("/:id/calendar", post(make_calendar_entry))
+        .route("/:id/calendar/:eid", get(get_calendar_entry))
+        .route("/:id/calendar/:eid", patch(update_calendar_entry))
+        .route("/:id/calendar/:eid", delete(delete_calendar_entry))
+        .route("/:id/calendar/:eid", post(update_calendar_entry))
+        .route("/:id/calendar/:eid/delete", post(delete_calendar_entry))
+        .route("/:id/role/:uid", put(set_role))
+        .route("/:id/invite-link", get(list_links))
+        .route("/:id/invite-link", post(create_link))
+        .route("/:id/invite-link/:token", delete(revoke_link))
+
+        .route("/:id/note", get(get_notes))
+        .route("/:id/note", post(make_note))
+        .route("/:id/note/:nid", get(get_note))
+        .route("/:id/note/:nid", put(update_note))
+        .route("/:id/note/:nid", delete(delete_note))
+}
+
+/// Roles of group members, each allowed what the ones below it are
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
+    // the column is checked to hold one of the above
+    pub fn from_db(role: &str) -> Self {
+        match role {
+            "owner" => Role::Owner,
+            "admin" => Role::Admin,
+            "editor" => Role::Editor,
+            _ => Role::Viewer,
+        }
+    }
+}
+
+/// What a member is trying to do to a group
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    // see its members, calendar and notes
+    Read,
+    // change its calendar and notes
+    Write,
+    // invite, remove and change the role of members below one's own
+    Manage,
+    // change, delete or hand over the group and reset its feed
+    Own,
+}
+
+impl Action {
+    fn needs(self) -> Role {
+        match self {
+            Action::Read => Role::Viewer,
+            Action::Write => Role::Editor,
+            Action::Manage => Role::Admin,
+            Action::Own => Role::Owner,
+        }
+    }
+}
+
+/// The member's role, or an error unless it allows `action`
+pub async fn authorize(
+    pool: &PgPool,
+    group_id: Uuid,
+    user_id: Uuid,
+    action: Action,
+) -> Result<Role, user::Error> {
+    let role = sqlx::query!(r#"
+SELECT role FROM meet.group_users
+WHERE group_id=$1 AND user_id=$2
+"#, group_id, user_id)
+        .fetch_optional(pool)
+        .await?
+        .map(|m| Role::from_db(&m.role))
+        .ok_or_else(|| user::Error::AuthError("Only members can access the group".to_owned()))?;
+    if role < action.needs() {
+        Err(user::Error::AuthError(format!(
+            "{action:?} needs the {} role or above, not {}",
+            action.needs().as_str(),
+            role.as_str()
+        )))?;
+    }
+    Ok(role)
+}
+
+#[derive(serde::Serialize, Debug)]
+struct IDResult {
+    id: i32,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CalendarQuery {
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+}
+
+/// The group's entries overlapping `from..to`
+async fn get_calendar_entries(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    Query(query): Query<CalendarQuery>,
+) -> Result<JSON<Vec<calendar::Occurrence>>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Read).await?;
+    let range = calendar::Range { from: query.from, to: query.to }.check()?;
+    Ok(axum::Json(calendar::in_range(&pool, None, &[group_id], range).await?))
+}
+
+async fn get_calendar_entry(
+    State(pool): State<PgPool>,
+    Path((group_id, entry_id)): Path<(Uuid, i32)>,
+    cookies: Cookies,
+) -> Result<JSON<entries::Entry>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Read).await?;
+    Ok(axum::Json(entries::fetch(&pool, Owner::Group(group_id), entry_id).await?))
+}
+
+async fn make_calendar_entry(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    Payload(submitted, entry): Payload<entries::Creator>,
+) -> Result<Response, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Write).await?;
+    // floating times are read in the writing member's zone
+    let zone = calendar::account_zone(&pool, acc.id).await?;
+    let created = entries::create(&pool, Owner::Group(group_id), &zone, entry).await;
+    let created = created.map(|created| IDResult { id: created.id });
+    submitted.reply(created).map_err(Error::from)
+}
+
+async fn update_calendar_entry(
+    State(pool): State<PgPool>,
+    Path((group_id, entry_id)): Path<(Uuid, i32)>,
+    cookies: Cookies,
+    Query(scope): Query<entries::EditScope>,
+    Payload(submitted, entry): Payload<entries::Updator>,
+) -> Result<Response, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Write).await?;
+    let updated = entries::update(&pool, Owner::Group(group_id), entry_id, scope, entry).await;
+    submitted.reply(updated).map_err(Error::from)
+}
+
+async fn delete_calendar_entry(
+    State(pool): State<PgPool>,
+    Path((group_id, entry_id)): Path<(Uuid, i32)>,
+    cookies: Cookies,
+    submitted: Submitted,
+    Query(scope): Query<entries::EditScope>,
+) -> Result<Response, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Write).await?;
+    let deleted = entries::delete(&pool, Owner::Group(group_id), entry_id, scope).await;
+    submitted.reply(deleted).map_err(Error::from)
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct RoleUpdator {
+    role: Role,
+}
+
+/// Changes the role of a member below one's own to another one below it,
+/// ownership is handed over through the group itself
+async fn set_role(
+    State(pool): State<PgPool>,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+    cookies: Cookies,
+    Json(entry): JSON<RoleUpdator>,
+) -> Result<StatusCode, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    let role = authorize(&pool, group_id, acc.id, Action::Manage).await?;
+    let current = authorize(&pool, group_id, user_id, Action::Read).await?;
+    if current >= role || entry.role >= role {
+        Err(AuthError(format!("An {} can only hand out roles below their own", role.as_str())))?;
+    }
+    sqlx::query!(r#"
+UPDATE meet.group_users
+SET role=$1
+WHERE group_id=$2 AND user_id=$3
+"#, entry.role.as_str(), group_id, user_id)
+        .execute(&pool)
+        .await?;
+    Ok(StatusCode::OK)
 }
 
+#[derive(serde::Serialize, Debug)]
+struct Note {
+    id: i32,
+    content: String,
+    // None once the account is gone
+    author_id: Option<Uuid>,
+    author_name: Option<String>,
+    last_edited_by: Option<Uuid>,
+    last_edited_by_name: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct NoteCreator {
+    content: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct NoteUpdator {
+    content: Option<String>,
+}
+
+async fn make_note(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(note): JSON<NoteCreator>,
+) -> Result<JSON<Note>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Write).await?;
+    sqlx::query_as!(Note, r#"
+WITH n AS (
+  INSERT INTO meet.group_notes
+    (content, group_id, author_id, last_edited_by)
+  VALUES
+    ($1, $2, $3, $3)
+  RETURNING *
+)
+SELECT
+  n.id as "id!", n.content as "content!",
+  n.author_id, a.name as "author_name?",
+  n.last_edited_by, e.name as "last_edited_by_name?"
+FROM n
+LEFT JOIN inter.accounts as a ON a.id=n.author_id
+LEFT JOIN inter.accounts as e ON e.id=n.last_edited_by
+"#, note.content, group_id, acc.id)
+        .fetch_one(&pool)
+        .await
+        .map(axum::Json)
+        .map_err(Error::from)
+}
+
+async fn get_note(
+    State(pool): State<PgPool>,
+    Path((group_id, note_id)): Path<(Uuid, i32)>,
+    cookies: Cookies,
+) -> Result<JSON<Note>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Read).await?;
+    sqlx::query_as!(Note, r#"
+SELECT
+  n.id, n.content,
+  n.author_id, a.name as "author_name?",
+  n.last_edited_by, e.name as "last_edited_by_name?"
+FROM meet.group_notes as n
+LEFT JOIN inter.accounts as a ON a.id=n.author_id
+LEFT JOIN inter.accounts as e ON e.id=n.last_edited_by
+WHERE n.id=$1 AND n.group_id=$2
+"#, note_id, group_id)
+        .fetch_one(&pool)
+        .await
+        .map(axum::Json)
+        .map_err(Error::from)
+}
+
+async fn update_note(
+    State(pool): State<PgPool>,
+    Path((group_id, note_id)): Path<(Uuid, i32)>,
+    cookies: Cookies,
+    Json(entry): JSON<NoteUpdator>,
+) -> Result<JSON<Note>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Write).await?;
+    sqlx::query_as!(Note, r#"
+WITH n AS (
+  UPDATE meet.group_notes
+  SET content=COALESCE($1, content), last_edited_by=$2
+  WHERE id=$3 AND group_id=$4
+  RETURNING *
+)
+SELECT
+  n.id as "id!", n.content as "content!",
+  n.author_id, a.name as "author_name?",
+  n.last_edited_by, e.name as "last_edited_by_name?"
+FROM n
+LEFT JOIN inter.accounts as a ON a.id=n.author_id
+LEFT JOIN inter.accounts as e ON e.id=n.last_edited_by
+"#, entry.content, acc.id, note_id, group_id)
+        .fetch_one(&pool)
+        .await
+        .map(axum::Json)
+        .map_err(Error::from)
+}
+
+async fn delete_note(
+    State(pool): State<PgPool>,
+    Path((group_id, note_id)): Path<(Uuid, i32)>,
+    cookies: Cookies,
+) -> Result<JSON<Note>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Write).await?;
+    sqlx::query_as!(Note, r#"
+WITH n AS (
+  DELETE FROM meet.group_notes
+  WHERE id=$1 AND group_id=$2
+  RETURNING *
+)
+SELECT
+  n.id as "id!", n.content as "content!",
+  n.author_id, a.name as "author_name?",
+  n.last_edited_by, e.name as "last_edited_by_name?"
+FROM n
+LEFT JOIN inter.accounts as a ON a.id=n.author_id
+LEFT JOIN inter.accounts as e ON e.id=n.last_edited_by
+"#, note_id, group_id)
+        .fetch_one(&pool)
+        .await
+        .map(axum::Json)
+        .map_err(Error::from)
+}
+
+async fn get_notes(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    Query(notes_query): Query<GetNoteEntries>,
+) -> Result<JSON<Paginate<Note>>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Read).await?;
+    let offset = notes_query.offset()?;
+    let page_index = notes_query.page;
+    let page_size = notes_query.page_size;
+    let entries_count = sqlx::query!(r#"
+SELECT COUNT(*) as "count!"
+FROM meet.group_notes
+WHERE group_id=$1
+"#, group_id)
+        .fetch_one(&pool)
+        .await?
+        .count;
+    let entries = sqlx::query_as!(Note, r#"
+SELECT
+  n.id, n.content,
+  n.author_id, a.name as "author_name?",
+  n.last_edited_by, e.name as "last_edited_by_name?"
+FROM meet.group_notes as n
+LEFT JOIN inter.accounts as a ON a.id=n.author_id
+LEFT JOIN inter.accounts as e ON e.id=n.last_edited_by
+WHERE n.group_id=$1
+ORDER BY n.id
+LIMIT $2 OFFSET $3
+"#, group_id, page_size, offset)
+        .fetch_all(&pool)
+        .await?;
+    Ok(axum::Json(Paginate::contain(
+        entries,
+        entries_count,
+        page_size,
+        page_index,
+    )))
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct LinkCreator {
+    // never expires or runs out when missing
+    expires_at: Option<DateTime<Utc>>,
+    max_uses: Option<i32>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct InviteLink {
+    token: String,
+    url: String,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    max_uses: Option<i32>,
+    uses: i32,
+}
+
+fn link_url(host: &Option<Host>, token: &str) -> String {
+    format!("{}/meet/join/{token}", public_url(host.as_ref().map(|h| h.0.as_str())))
+}
+
+/// Creates a link that lets any logged in account join the group as a viewer
+async fn create_link(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    host: Option<Host>,
+    Json(entry): JSON<LinkCreator>,
+) -> Result<JSON<InviteLink>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Manage).await?;
+    if entry.expires_at.is_some_and(|at| at <= Utc::now()) {
+        Err(LogicError("Invite links can't expire in the past".to_owned()))?;
+    }
+    if entry.max_uses.is_some_and(|uses| uses < 1) {
+        Err(LogicError("Invite links need at least one use".to_owned()))?;
+    }
+    let token = ical::new_token()?;
+    let link = sqlx::query!(r#"
+INSERT INTO meet.group_invite_links
+  (token, group_id, created_by, expires_at, max_uses)
+VALUES
+  ($1, $2, $3, $4, $5)
+RETURNING token, created_at, expires_at, max_uses, uses
+"#, token, group_id, acc.id, entry.expires_at, entry.max_uses)
+        .fetch_one(&pool)
+        .await?;
+    Ok(axum::Json(InviteLink {
+        url: link_url(&host, &link.token),
+        token: link.token,
+        created_at: link.created_at,
+        expires_at: link.expires_at,
+        max_uses: link.max_uses,
+        uses: link.uses,
+    }))
+}
+
+/// The group's links that weren't revoked and haven't expired or run out
+async fn list_links(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    host: Option<Host>,
+) -> Result<JSON<Vec<InviteLink>>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Manage).await?;
+    let links = sqlx::query!(r#"
+SELECT token, created_at, expires_at, max_uses, uses
+FROM meet.group_invite_links
+WHERE group_id=$1 AND NOT revoked
+  AND (expires_at IS NULL OR expires_at > now())
+  AND (max_uses IS NULL OR uses < max_uses)
+ORDER BY created_at
+"#, group_id)
+        .fetch_all(&pool)
+        .await?
+        .into_iter()
+        .map(|link| InviteLink {
+            url: link_url(&host, &link.token),
+            token: link.token,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            max_uses: link.max_uses,
+            uses: link.uses,
+        })
+        .collect();
+    Ok(axum::Json(links))
+}
+
+async fn revoke_link(
+    State(pool): State<PgPool>,
+    Path((group_id, token)): Path<(Uuid, String)>,
+    cookies: Cookies,
+) -> Result<StatusCode, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    authorize(&pool, group_id, acc.id, Action::Manage).await?;
+    sqlx::query!(r#"
+UPDATE meet.group_invite_links
+SET revoked=true
+WHERE token=$1 AND group_id=$2
+RETURNING token
+"#, token, group_id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(UnknownLink)?;
+    Ok(StatusCode::OK)
+}
+
+/// Id and name of the group the link still lets people join
+async fn usable_link(pool: &PgPool, token: &str) -> Result<Option<(Uuid, String)>, Error> {
+    Ok(sqlx::query!(r#"
+SELECT g.id, g.name
+FROM meet.group_invite_links as l
+INNER JOIN meet.groups as g ON g.id=l.group_id
+WHERE l.token=$1 AND NOT l.revoked
+  AND (l.expires_at IS NULL OR l.expires_at > now())
+  AND (l.max_uses IS NULL OR l.uses < l.max_uses)
+"#, token)
+        .fetch_optional(pool)
+        .await?
+        .map(|g| (g.id, g.name)))
+}
+
+/// Asks whoever opened an invite link to confirm joining the group
+pub async fn join_page(
+    State(pool): State<PgPool>,
+    Path(token): Path<String>,
+    cookies: Cookies,
+) -> Result<Markup, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await.ok();
+    let link = usable_link(&pool, &token).await?;
+    let member = match (&acc, &link) {
+        (Some(acc), Some((group_id, _))) => {
+            authorize(&pool, *group_id, acc.id, Action::Read).await.is_ok()
+        }
+        _ => false,
+    };
+    Ok(html! {
+        (DOCTYPE);
+        head {
+            (CSS("/files/style.css"));
+        }
+        body {
+            (nav("/meet/join", &cookies, &pool).await);
+            div.center #"content" {
+                @match (&acc, &link) {
+                    (_, None) => p { "This invite link is invalid, expired or used up." },
+                    (None, Some((_, name))) => p {
+                        "Log in to join " b { (name) } ". "
+                        a href="/accounts/login" { "Login" }
+                    },
+                    (Some(_), Some((_, name))) if member => p {
+                        "You're already a member of " b { (name) } ". "
+                        a href="/meet/user" { "Meet" }
+                    },
+                    (Some(_), Some((_, name))) => form method="POST" action={ "/meet/join/" (token) } {
+                        p { "Join " b { (name) } "?" }
+                        button { "Join" }
+                    },
+                }
+            }
+        }
+    })
+}
+
+/// Joins the link's group as a viewer, using the link up once
+pub async fn join(
+    State(pool): State<PgPool>,
+    Path(token): Path<String>,
+    cookies: Cookies,
+) -> Result<Redirect, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    let (group_id, _) = usable_link(&pool, &token).await?.ok_or(UnknownLink)?;
+    if authorize(&pool, group_id, acc.id, Action::Read).await.is_ok() {
+        return Ok(Redirect::to("/meet/user"));
+    }
+    let mut tx = pool.begin().await?;
+    // checked again here so concurrent joins can't go over max_uses
+    sqlx::query!(r#"
+UPDATE meet.group_invite_links
+SET uses=uses+1
+WHERE token=$1 AND NOT revoked
+  AND (expires_at IS NULL OR expires_at > now())
+  AND (max_uses IS NULL OR uses < max_uses)
+RETURNING token
+"#, token)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(UnknownLink)?;
+    sqlx::query!(r#"
+INSERT INTO meet.group_users
+  (group_id, user_id)
+VALUES
+  ($1, $2)
+"#, group_id, acc.id)
+        .execute(&mut *tx)
+        .await?;
+    // a pending invite to the same group has nothing left to do
+    sqlx::query!(r#"
+DELETE FROM meet.group_invites
+WHERE group_id=$1 AND user_id=$2
+"#, group_id, acc.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(Redirect::to("/meet/user"))
+}
@@ -0,0 +1,803 @@
+//! iCalendar (RFC 5545) subscription feeds.
+//!
+//! Every account and group can get a secret token; whoever knows
+//! `/meet/feed/<token>.ics` can subscribe to that calendar read-only, so
+//! regenerating the token is how access is taken back.
+//!
+//! Times are written in the entry's zone with its IANA name as `TZID` and
+//! no `VTIMEZONE` blocks, which calendar clients resolve on their own.
+//! Occurrences edited on their own share the UID of their series and are
+//! told apart by `RECURRENCE-ID`.
+use super::calendar::{self, Owner};
+use super::user::Error::{self, *};
+use crate::*;
+use axum::{
+    extract::{Host, Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+
+const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+// lines are folded after this many octets, not counting the CRLF
+const MAX_LINE: usize = 75;
+
+pub fn new_token() -> Result<String, Error> {
+    let mut bytes = [0; 24];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| LogicError(e.to_string()))?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// The feed's token, created on first use or replaced if `regenerate`
+pub async fn token(pool: &PgPool, owner: Owner, regenerate: bool) -> Result<String, Error> {
+    let token = new_token()?;
+    // one upsert, so concurrent first uses agree on a token instead of racing
+    let stored = match owner {
+        Owner::Account(id) => sqlx::query!("
+INSERT INTO meet.calendar_feeds (token, account_id)
+VALUES ($1, $2)
+ON CONFLICT (account_id) DO UPDATE SET
+    token=CASE WHEN $3 THEN $1 ELSE meet.calendar_feeds.token END,
+    created_at=CASE WHEN $3 THEN now() ELSE meet.calendar_feeds.created_at END
+RETURNING token
+", token, id, regenerate)
+            .fetch_one(pool)
+            .await?
+            .token,
+        Owner::Group(id) => sqlx::query!("
+INSERT INTO meet.calendar_feeds (token, group_id)
+VALUES ($1, $2)
+ON CONFLICT (group_id) DO UPDATE SET
+    token=CASE WHEN $3 THEN $1 ELSE meet.calendar_feeds.token END,
+    created_at=CASE WHEN $3 THEN now() ELSE meet.calendar_feeds.created_at END
+RETURNING token
+", token, id, regenerate)
+            .fetch_one(pool)
+            .await?
+            .token,
+    };
+    Ok(stored)
+}
+
+pub fn url(host: Option<&str>, token: &str) -> String {
+    format!("{}/meet/feed/{token}.ics", public_url(host))
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct FeedInfo {
+    url: String,
+}
+
+impl FeedInfo {
+    pub fn new(host: Option<Host>, token: &str) -> Self {
+        Self { url: url(host.as_ref().map(|h| h.0.as_str()), token) }
+    }
+}
+
+/// An entry of either calendar table, as the feed needs it
+pub struct Event {
+    pub id: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub all_day: bool,
+    pub time_zone: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub rrule: Option<String>,
+    pub exdates: Vec<DateTime<Utc>>,
+    pub parent_id: Option<i32>,
+    pub recurrence_at: Option<DateTime<Utc>>,
+    // imported UID of the entry or its series
+    pub uid: Option<String>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+/// Appends `line` folded to RFC 5545's line length, never splitting a character
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            // the leading space counts towards the continuation line
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// `DTSTART`-like property for `time` in `zone`, a date for all-day entries
+fn time_prop(name: &str, zone: Tz, all_day: bool, time: DateTime<Utc>) -> String {
+    let local = calendar::to_local(zone, time);
+    if all_day {
+        format!("{name};VALUE=DATE:{}", local.format("%Y%m%d"))
+    } else if zone == Tz::UTC {
+        format!("{name}:{}", time.format("%Y%m%dT%H%M%SZ"))
+    } else {
+        format!("{name};TZID={}:{}", zone.name(), local.format("%Y%m%dT%H%M%S"))
+    }
+}
+
+fn push_event(out: &mut String, uid_prefix: &str, domain: &str, stamp: &str, event: &Event) {
+    let zone = calendar::parse_zone(&event.time_zone).unwrap_or(Tz::UTC);
+    let series = event.parent_id.unwrap_or(event.id);
+    push_line(out, "BEGIN:VEVENT");
+    match &event.uid {
+        Some(uid) => push_line(out, &format!("UID:{}", escape(uid))),
+        None => push_line(out, &format!("UID:{uid_prefix}-{series}@{domain}")),
+    }
+    push_line(out, &format!("DTSTAMP:{stamp}"));
+    push_line(out, &time_prop("DTSTART", zone, event.all_day, event.starts_at));
+    push_line(out, &time_prop("DTEND", zone, event.all_day, event.ends_at));
+    push_line(out, &format!("SUMMARY:{}", escape(&event.title)));
+    if let Some(description) = &event.description {
+        push_line(out, &format!("DESCRIPTION:{}", escape(description)));
+    }
+    if let Some(rule) = &event.rrule {
+        push_line(out, &format!("RRULE:{rule}"));
+        for exdate in &event.exdates {
+            push_line(out, &time_prop("EXDATE", zone, event.all_day, *exdate));
+        }
+    }
+    if let (Some(_), Some(recurrence_at)) = (event.parent_id, event.recurrence_at) {
+        push_line(out, &time_prop("RECURRENCE-ID", zone, event.all_day, recurrence_at));
+    }
+    push_line(out, "END:VEVENT");
+}
+
+/// A whole `VCALENDAR`; UIDs are `<uid_prefix>-<id>@<domain>`
+pub fn render(name: &str, uid_prefix: &str, domain: &str, events: &[Event]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//sr-rs//meet//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        push_event(&mut out, uid_prefix, domain, &stamp, event);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+pub fn domain(host: Option<&str>) -> String {
+    let url = public_url(host);
+    let host = url.split_once("://").map(|(_, rest)| rest).unwrap_or(&url);
+    host.split(['/', ':']).next().unwrap_or(host).to_owned()
+}
+
+/// Serves `/meet/feed/<token>.ics`, the extension is optional
+pub async fn feed(
+    State(pool): State<PgPool>,
+    host: Option<Host>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    let feed = sqlx::query!("
+SELECT f.account_id, f.group_id, COALESCE(a.name, g.name) as \"name!\"
+FROM meet.calendar_feeds as f
+LEFT JOIN inter.accounts as a ON a.id=f.account_id
+LEFT JOIN meet.groups as g ON g.id=f.group_id
+WHERE f.token=$1
+", token)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or(UnknownFeed)?;
+    let (uid_prefix, events) = match (feed.account_id, feed.group_id) {
+        (Some(account_id), _) => ("user", sqlx::query_as!(Event, "
+SELECT
+    e.id, e.starts_at, e.ends_at, e.all_day, e.time_zone, e.title, e.description,
+    e.rrule, e.exdates, e.parent_id, e.recurrence_at, COALESCE(e.uid, p.uid) as uid
+FROM meet.user_calendar_entries as e
+LEFT JOIN meet.user_calendar_entries as p ON p.id=e.parent_id
+WHERE e.owner_id=$1
+ORDER BY e.starts_at
+", account_id)
+            .fetch_all(&pool)
+            .await?),
+        (_, group_id) => ("group", sqlx::query_as!(Event, "
+SELECT
+    e.id, e.starts_at, e.ends_at, e.all_day, e.time_zone, e.title, e.description,
+    e.rrule, e.exdates, e.parent_id, e.recurrence_at, COALESCE(e.uid, p.uid) as uid
+FROM meet.group_calendar_entries as e
+LEFT JOIN meet.group_calendar_entries as p ON p.id=e.parent_id
+WHERE e.group_id=$1
+ORDER BY e.starts_at
+", group_id)
+            .fetch_all(&pool)
+            .await?),
+    };
+    let domain = domain(host.as_ref().map(|h| h.0.as_str()));
+    let body = render(&feed.name, uid_prefix, &domain, &events);
+    Ok((
+        [
+            (header::CONTENT_TYPE, CONTENT_TYPE.to_owned()),
+            (header::CONTENT_DISPOSITION, "inline; filename=\"calendar.ics\"".to_owned()),
+        ],
+        body,
+    ))
+}
+
+/// A content line, `NAME;PARAM=value:VALUE`
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Component {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Component>,
+}
+
+impl Component {
+    fn get(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(|p| unescape(&p.value))
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+fn parse_line(line: &str) -> Option<Property> {
+    // the value starts at the first colon outside a quoted parameter
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(i),
+            _ => {}
+        }
+        None
+    })?;
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(n, v)| (n.trim().to_ascii_uppercase(), v.trim_matches('"').to_owned()))
+        .collect();
+    Some(Property { name, params, value: line[colon + 1..].to_owned() })
+}
+
+/// The top-level components of `text`, usually a single `VCALENDAR`
+fn parse(text: &str) -> Result<Vec<Component>, Error> {
+    let unfolded = text
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut stack: Vec<Component> = vec![];
+    let mut top = vec![];
+    for (i, line) in unfolded.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: &str| InvalidEntry(format!("line {}: {reason}", i + 1));
+        let property = parse_line(line).ok_or_else(|| invalid("not a content line"))?;
+        match property.name.as_str() {
+            "BEGIN" => stack.push(Component {
+                name: property.value.trim().to_ascii_uppercase(),
+                ..Default::default()
+            }),
+            "END" => {
+                let component = stack.pop().ok_or_else(|| invalid("END without BEGIN"))?;
+                if component.name != property.value.trim().to_ascii_uppercase() {
+                    return Err(invalid(&format!("END doesn't close {}", component.name)));
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(component),
+                    None => top.push(component),
+                }
+            }
+            _ => stack
+                .last_mut()
+                .ok_or_else(|| invalid("property outside of a component"))?
+                .properties
+                .push(property),
+        }
+    }
+    if let Some(open) = stack.last() {
+        return Err(InvalidEntry(format!("{} is never closed", open.name)));
+    }
+    Ok(top)
+}
+
+enum Time {
+    Date(NaiveDate),
+    Local(NaiveDateTime, Tz),
+}
+
+impl Time {
+    fn parse(value: &str, tzid: Option<&str>, default: Tz) -> Result<Self, String> {
+        let value = value.trim();
+        let invalid = || format!("invalid date or time \"{value}\"");
+        if value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(Time::Date)
+                .or(Err(invalid()));
+        }
+        let (value, zone) = match (value.strip_suffix('Z'), tzid) {
+            (Some(utc), _) => (utc, Tz::UTC),
+            // zones are looked up by IANA name, VTIMEZONE definitions are ignored
+            (None, Some(tzid)) => (value, calendar::parse_zone(tzid).map_err(|e| e.to_string())?),
+            // floating times are read in the calendar's own zone
+            (None, None) => (value, default),
+        };
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .map(|t| Time::Local(t, zone))
+            .or(Err(invalid()))
+    }
+
+    fn of(property: &Property, default: Tz) -> Result<Self, String> {
+        Self::parse(&property.value, property.param("TZID"), default)
+    }
+
+    fn to_utc(&self, zone: Tz) -> Result<DateTime<Utc>, String> {
+        match self {
+            Time::Date(date) => calendar::to_utc(zone, date.and_time(NaiveTime::MIN)),
+            Time::Local(time, zone) => calendar::to_utc(*zone, *time),
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// `P1W`, `P1DT2H`, `PT30M` and the like, `None` also when it overflows
+fn duration(value: &str) -> Option<TimeDelta> {
+    let value = value.trim().trim_start_matches('+');
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                let part = match unit {
+                    'W' => TimeDelta::try_weeks(n),
+                    'D' => TimeDelta::try_days(n),
+                    'H' => TimeDelta::try_hours(n),
+                    'M' => TimeDelta::try_minutes(n),
+                    'S' => TimeDelta::try_seconds(n),
+                    _ => return None,
+                };
+                total = total.checked_add(&part?)?;
+            }
+        }
+    }
+    Some(if negative { -total } else { total })
+}
+
+struct Imported {
+    uid: Option<String>,
+    recurrence_id: Option<DateTime<Utc>>,
+    span: calendar::Span,
+    title: String,
+    description: Option<String>,
+    rrule: Option<String>,
+    exdates: Vec<DateTime<Utc>>,
+}
+
+fn to_event(event: &Component, default: Tz) -> Result<Imported, String> {
+    let dtstart = event.get("DTSTART").ok_or("no DTSTART")?;
+    let start = Time::of(dtstart, default)?;
+    let (start, zone, all_day) = match start {
+        Time::Date(date) => (date.and_time(NaiveTime::MIN), default, true),
+        Time::Local(time, zone) => (time, zone, false),
+    };
+    let end = match (event.get("DTEND"), event.get("DURATION")) {
+        (Some(dtend), _) => {
+            let end = Time::of(dtend, zone)?.to_utc(zone)?;
+            Some(calendar::to_local(zone, end))
+        }
+        (None, Some(length)) => {
+            let end = duration(&length.value).and_then(|length| start.checked_add_signed(length));
+            Some(end.ok_or("invalid DURATION")?)
+        }
+        (None, None) => None,
+    };
+    // all-day ends are exclusive here but inclusive for `Span::new`
+    let end = match (end, all_day) {
+        (Some(end), true) => Some(
+            end.checked_sub_signed(TimeDelta::days(1))
+                .ok_or("end date out of range")?,
+        ),
+        (end, _) => end,
+    };
+    let span = calendar::Span::new(
+        calendar::Local(start),
+        end.map(calendar::Local),
+        all_day,
+        zone.name(),
+    )
+    .map_err(|e| e.to_string())?;
+    let rrule = match event.get("RRULE") {
+        Some(rule) => Some(calendar::check_rule(&span, &rule.value).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let mut exdates = vec![];
+    for exdate in event.properties.iter().filter(|p| p.name == "EXDATE") {
+        for value in exdate.value.split(',') {
+            exdates.push(Time::parse(value, exdate.param("TZID"), zone)?.to_utc(zone)?);
+        }
+    }
+    let recurrence_id = match event.get("RECURRENCE-ID") {
+        Some(id) => Some(Time::of(id, zone)?.to_utc(zone)?),
+        None => None,
+    };
+    Ok(Imported {
+        uid: event.text("UID").filter(|uid| !uid.is_empty()),
+        recurrence_id,
+        span,
+        title: event.text("SUMMARY").unwrap_or_default(),
+        description: event.text("DESCRIPTION").filter(|d| !d.is_empty()),
+        rrule,
+        exdates,
+    })
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct Preview {
+    uid: Option<String>,
+    title: String,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    all_day: bool,
+    time_zone: String,
+    rrule: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct Skipped {
+    component: String,
+    uid: Option<String>,
+    reason: String,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct ImportReport {
+    dry_run: bool,
+    imported: Vec<Preview>,
+    // UIDs that were already in the calendar
+    duplicates: Vec<String>,
+    skipped: Vec<Skipped>,
+}
+
+/// Id of the entry `uid` refers to, also matching UIDs this instance exported
+async fn find_uid(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    domain: &str,
+    uid: &str,
+) -> Result<Option<i32>, Error> {
+    Ok(match owner {
+        Owner::Account(id) => sqlx::query!("
+SELECT id FROM meet.user_calendar_entries
+WHERE owner_id=$1 AND parent_id IS NULL AND (uid=$2 OR 'user-' || id || '@' || $3 = $2)
+", id, uid, domain)
+            .fetch_optional(conn)
+            .await?
+            .map(|r| r.id),
+        Owner::Group(id) => sqlx::query!("
+SELECT id FROM meet.group_calendar_entries
+WHERE group_id=$1 AND parent_id IS NULL AND (uid=$2 OR 'group-' || id || '@' || $3 = $2)
+", id, uid, domain)
+            .fetch_optional(conn)
+            .await?
+            .map(|r| r.id),
+    })
+}
+
+async fn insert(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    event: &Imported,
+    parent_id: Option<i32>,
+) -> Result<(), Error> {
+    // occurrences take the UID of their series
+    let uid = event.uid.as_ref().filter(|_| parent_id.is_none());
+    let span = &event.span;
+    match owner {
+        Owner::Account(id) => sqlx::query!("
+INSERT INTO meet.user_calendar_entries
+(owner_id, starts_at, ends_at, all_day, time_zone, title, description,
+ rrule, exdates, parent_id, recurrence_at, uid)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+", id, span.starts_at, span.ends_at, span.all_day, span.time_zone, event.title,
+    event.description, event.rrule, &event.exdates, parent_id, event.recurrence_id, uid)
+            .execute(&mut *conn)
+            .await?,
+        Owner::Group(id) => sqlx::query!("
+INSERT INTO meet.group_calendar_entries
+(group_id, starts_at, ends_at, all_day, time_zone, title, description,
+ rrule, exdates, parent_id, recurrence_at, uid)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+", id, span.starts_at, span.ends_at, span.all_day, span.time_zone, event.title,
+    event.description, event.rrule, &event.exdates, parent_id, event.recurrence_id, uid)
+            .execute(&mut *conn)
+            .await?,
+    };
+    Ok(())
+}
+
+/// Whether the series already has an entry for the occurrence at `at`
+async fn has_occurrence(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    parent_id: i32,
+    at: DateTime<Utc>,
+) -> Result<bool, Error> {
+    Ok(match owner {
+        Owner::Account(_) => sqlx::query!("
+SELECT id FROM meet.user_calendar_entries WHERE parent_id=$1 AND recurrence_at=$2
+", parent_id, at)
+            .fetch_optional(conn)
+            .await?
+            .is_some(),
+        Owner::Group(_) => sqlx::query!("
+SELECT id FROM meet.group_calendar_entries WHERE parent_id=$1 AND recurrence_at=$2
+", parent_id, at)
+            .fetch_optional(conn)
+            .await?
+            .is_some(),
+    })
+}
+
+/// Leaves the occurrence at `at` out of the series, an entry of its own replaces it
+async fn exclude(
+    conn: &mut sqlx::PgConnection,
+    owner: Owner,
+    parent_id: i32,
+    at: DateTime<Utc>,
+) -> Result<(), Error> {
+    match owner {
+        Owner::Account(_) => sqlx::query!("
+UPDATE meet.user_calendar_entries
+SET exdates=array_append(exdates, $2)
+WHERE id=$1 AND NOT $2=ANY(exdates)
+", parent_id, at)
+            .execute(conn)
+            .await?,
+        Owner::Group(_) => sqlx::query!("
+UPDATE meet.group_calendar_entries
+SET exdates=array_append(exdates, $2)
+WHERE id=$1 AND NOT $2=ANY(exdates)
+", parent_id, at)
+            .execute(conn)
+            .await?,
+    };
+    Ok(())
+}
+
+/// Imports the `VEVENT`s of `text` into `owner`'s calendar, skipping UIDs
+/// it already has. A dry run does the same but rolls everything back, so
+/// its report is exactly what a real import would do.
+pub async fn import(
+    pool: &PgPool,
+    owner: Owner,
+    default_zone: &str,
+    domain: &str,
+    text: &str,
+    dry_run: bool,
+) -> Result<ImportReport, Error> {
+    let default = calendar::parse_zone(default_zone)?;
+    let mut report = ImportReport { dry_run, ..Default::default() };
+    let mut events = vec![];
+    let roots = parse(text)?;
+    for root in &roots {
+        if root.name != "VCALENDAR" {
+            return Err(InvalidEntry(format!("expected VCALENDAR, found {}", root.name)));
+        }
+        for component in &root.children {
+            match component.name.as_str() {
+                "VEVENT" => events.push(component),
+                "VTIMEZONE" => {}
+                other => report.skipped.push(Skipped {
+                    component: other.to_owned(),
+                    uid: component.text("UID"),
+                    reason: "only events are imported".to_owned(),
+                }),
+            }
+        }
+    }
+    let mut parsed = vec![];
+    for event in events {
+        let uid = event.text("UID");
+        for child in &event.children {
+            report.skipped.push(Skipped {
+                component: child.name.clone(),
+                uid: uid.clone(),
+                reason: "dropped, the event itself is imported".to_owned(),
+            });
+        }
+        match to_event(event, default) {
+            Ok(imported) => parsed.push(imported),
+            Err(reason) => report.skipped.push(Skipped {
+                component: "VEVENT".to_owned(),
+                uid,
+                reason,
+            }),
+        }
+    }
+    // series go first so edited occurrences find them
+    parsed.sort_by_key(|e| e.recurrence_id.is_some());
+
+    let mut tx = pool.begin().await?;
+    for event in parsed {
+        let existing = match &event.uid {
+            Some(uid) => find_uid(&mut tx, owner, domain, uid).await?,
+            None => None,
+        };
+        let parent_id = match (event.recurrence_id, existing) {
+            (None, Some(_)) => {
+                report.duplicates.extend(event.uid.clone());
+                continue;
+            }
+            (None, None) => None,
+            (Some(at), Some(parent_id)) => {
+                if has_occurrence(&mut tx, owner, parent_id, at).await? {
+                    report.duplicates.extend(event.uid.clone());
+                    continue;
+                }
+                exclude(&mut tx, owner, parent_id, at).await?;
+                Some(parent_id)
+            }
+            (Some(_), None) => {
+                report.skipped.push(Skipped {
+                    component: "VEVENT".to_owned(),
+                    uid: event.uid.clone(),
+                    reason: "RECURRENCE-ID of a series that isn't in the calendar".to_owned(),
+                });
+                continue;
+            }
+        };
+        insert(&mut tx, owner, &event, parent_id).await?;
+        report.imported.push(Preview {
+            uid: event.uid,
+            title: event.title,
+            starts_at: event.span.starts_at,
+            ends_at: event.span.ends_at,
+            all_day: event.span.all_day,
+            time_zone: event.span.time_zone,
+            rrule: event.rrule,
+        });
+    }
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(lines: &[&str]) -> Result<Imported, String> {
+        let text = ["BEGIN:VCALENDAR", "BEGIN:VEVENT"]
+            .iter()
+            .chain(lines)
+            .chain(&["END:VEVENT", "END:VCALENDAR"])
+            .map(|line| format!("{line}\r\n"))
+            .collect::<String>();
+        let calendar = parse(&text).map_err(|e| e.to_string())?;
+        to_event(&calendar[0].children[0], Tz::Europe__Berlin)
+    }
+
+    #[test]
+    fn folds_multibyte_text_at_75_octets() {
+        let summary = format!("SUMMARY:{}", "äö€🙂".repeat(20));
+        let mut out = String::new();
+        push_line(&mut out, &summary);
+        let lines: Vec<_> = out.split_terminator("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE));
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        // every line but the last is filled up to where the next character wouldn't fit
+        assert!(lines[..lines.len() - 1].iter().all(|line| line.len() > MAX_LINE - 4));
+        let calendar = parse(&format!("BEGIN:VEVENT\r\n{out}END:VEVENT\r\n")).unwrap();
+        assert_eq!(calendar[0].get("SUMMARY").unwrap().value, "äö€🙂".repeat(20));
+    }
+
+    #[test]
+    fn escape_round_trips() {
+        let text = "a\\b; c, d\ne\\nf";
+        assert_eq!(escape(text), r"a\\b\; c\, d\ne\\nf");
+        assert_eq!(unescape(&escape(text)), text);
+        // only \n survives as a line break
+        assert_eq!(unescape(&escape("a\r\nb\rc")), "a\nb\nc");
+        assert_eq!(unescape("a\\Nb\\"), "a\nb");
+    }
+
+    #[test]
+    fn reads_durations() {
+        assert_eq!(duration("P1W"), Some(TimeDelta::weeks(1)));
+        assert_eq!(duration("+P1DT2H30M"), Some(TimeDelta::minutes(26 * 60 + 30)));
+        assert_eq!(duration("-PT15M"), Some(TimeDelta::minutes(-15)));
+        assert_eq!(duration("PT"), Some(TimeDelta::zero()));
+        assert_eq!(duration("P1X"), None);
+        assert_eq!(duration("1H"), None);
+        assert_eq!(duration("P99999999999999999999D"), None);
+        assert_eq!(duration("P9999999999999999W"), None);
+        // each part fits, their sum doesn't
+        assert_eq!(duration("P100000000000D100000000000D"), None);
+    }
+
+    #[test]
+    fn skips_bad_durations() {
+        let start = "DTSTART:20260401T100000Z";
+        let ok = event(&[start, "DURATION:PT90M"]).unwrap();
+        assert_eq!(ok.span.ends_at - ok.span.starts_at, TimeDelta::minutes(90));
+        let negative = event(&[start, "DURATION:-PT1H"]).err().unwrap();
+        assert!(negative.contains("end after they start"), "{negative}");
+        let overflowing = event(&[start, "DURATION:P9999999999W"]).err().unwrap();
+        assert_eq!(overflowing, "invalid DURATION");
+    }
+
+    #[test]
+    fn all_day_ends_are_exclusive_in_files() {
+        let imported = event(&[
+            "DTSTART;VALUE=DATE:20260328",
+            "DTEND;VALUE=DATE:20260330",
+            "SUMMARY:weekend",
+        ])
+        .unwrap();
+        let span = imported.span;
+        assert!(span.all_day);
+        let zone = Tz::Europe__Berlin;
+        let local = |s| calendar::Local::parse(s).unwrap();
+        // the form names the last day, Sunday
+        let form = calendar::Span::new(local("2026-03-28"), Some(local("2026-03-29")), true, zone.name());
+        assert_eq!(span, form.unwrap());
+        assert_eq!(span.update(None, None, None, None).unwrap(), span);
+        assert_eq!(time_prop("DTEND", zone, true, span.ends_at), "DTEND;VALUE=DATE:20260330");
+        // a single day without DTEND
+        let day = event(&["DTSTART;VALUE=DATE:20260328"]).unwrap().span;
+        assert_eq!(time_prop("DTEND", zone, true, day.ends_at), "DTEND;VALUE=DATE:20260329");
+    }
+}
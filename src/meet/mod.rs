@@ -1,5 +1,5 @@
 use crate::*;
-use axum::Router;
+use axum::{routing::{get, post}, Router};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -11,9 +11,16 @@ pub enum Error {
 
 pub mod user;
 pub mod group;
+pub mod calendar;
+pub mod entries;
+pub mod ical;
+pub mod views;
 
 pub fn service() -> Router<PgPool> {
     Router::new()
         .nest("/user", user::service())
         .nest("/group", group::service())
+        .route("/feed/:token", get(ical::feed))
+        .route("/join/:token", get(group::join_page))
+        .route("/join/:token", post(group::join))
 }
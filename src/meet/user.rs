@@ -8,6 +8,14 @@ pub enum Error {
     AuthError(String),
     #[error("Business logic Error: {0}")]
     LogicError(String),
+    #[error("Invalid calendar entry: {0}")]
+    InvalidEntry(String),
+    #[error("No calendar feed with this token")]
+    UnknownFeed,
+    #[error("No account named {0}")]
+    UnknownUser(String),
+    #[error("Invalid page: {0}")]
+    InvalidPage(String),
 }
 use self::Error::*;
 use axum::http::StatusCode;
@@ -21,6 +29,10 @@ impl DescribeError for Error {
             SqlxError(_) => StatusCode::BAD_REQUEST,
             AuthError(_) => StatusCode::FORBIDDEN,
             LogicError(_) => StatusCode::FORBIDDEN,
+            InvalidEntry(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            UnknownFeed => StatusCode::NOT_FOUND,
+            UnknownUser(_) => StatusCode::NOT_FOUND,
+            InvalidPage(_) => StatusCode::BAD_REQUEST,
         };
         ( code, format!("{self:?}") )
     }
@@ -29,31 +41,38 @@ impl DescribeError for Error {
 //TODO SqlxError actually handle different db errors
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        format!("{:?}", self).into_response()
+        (self.code(), self.message()).into_response()
     }
 }
 
 use crate::*;
 use axum::{
     extract::*,
-    response::{IntoResponse, Json as JSON},
+    response::{IntoResponse, Json as JSON, Response},
     routing::{delete, get, patch, post, put},
     Router,
 };
 use tower_cookies::Cookies;
-
-async fn tdep() -> impl IntoResponse {
-    html! {"TODO"}
-}
+use super::views::{self, Payload, Submitted};
+use super::calendar::{self, Owner};
+use super::group::{self, Action, Role};
+use super::{entries, ical};
 
 pub fn service() -> Router<PgPool> {
     Router::new()
         .route("/", get(index))
-        .route("/self", get(tdep))
+        .route("/self", get(get_self))
+        .route("/self", patch(update_self))
 
+        .route("/calendar/view", get(calendar_view))
+        .route("/calendar/feed", get(get_calendar_feed))
+        .route("/calendar/feed", post(regenerate_calendar_feed))
+        .route("/calendar/import", post(import_calendar))
         .route("/calendar/:id", get(get_calendar_entry))
         .route("/calendar/:id", patch(update_calendar_entry))
         .route("/calendar/:id", delete(delete_calendar_entry))
+        .route("/calendar/:id", post(update_calendar_entry))
+        .route("/calendar/:id/delete", post(delete_calendar_entry))
         .route("/calendar", post(make_calendar_entry))
         .route("/calendar", get(get_calendar_entries))
 
@@ -69,15 +88,70 @@ pub fn service() -> Router<PgPool> {
         .route("/group/:id", delete(delete_group))
         .route("/group/:id", patch(update_group))
         .route("/group/:id/users", get(list_group_users))
+        .route("/group/:id/feed", get(get_group_feed))
+        .route("/group/:id/feed", post(regenerate_group_feed))
+        .route("/group/:id/calendar/import", post(import_group_calendar))
         .route("/group/:id/user/:uid", delete(remove_user))
         .route("/group/:id/user/:uid", post(invite_to_group))
+        .route("/group/:id/invite", post(invite_by_name))
         .route("/group/invites", get(list_invites))
         .route("/group/invite/:id", post(answer_invite))
 }
 
 
+#[derive(serde::Serialize)]
+struct SelfInfo {
+    id: Uuid,
+    name: String,
+    time_zone: String,
+}
+
+#[derive(serde::Deserialize)]
+struct SelfUpdator {
+    time_zone: Option<String>,
+}
+
+async fn get_self(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+) -> Result<JSON<SelfInfo>, Error> {
+    let id = crate::accounts::get_id(&cookies)?;
+    sqlx::query_as!(SelfInfo, "
+SELECT id, name, time_zone
+FROM inter.accounts
+WHERE id=$1
+", id)
+        .fetch_one(&pool)
+        .await
+        .map(axum::Json)
+        .map_err(Error::from)
+}
+
+async fn update_self(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    Json(info): JSON<SelfUpdator>,
+) -> Result<JSON<SelfInfo>, Error> {
+    let id = crate::accounts::get_id(&cookies)?;
+    let time_zone = match info.time_zone {
+        Some(time_zone) => Some(calendar::parse_zone(&time_zone)?.name().to_owned()),
+        None => None,
+    };
+    sqlx::query_as!(SelfInfo, "
+UPDATE inter.accounts as a
+SET time_zone=COALESCE($1, a.time_zone)
+WHERE id=$2
+RETURNING id, name, time_zone
+", time_zone, id)
+        .fetch_one(&pool)
+        .await
+        .map(axum::Json)
+        .map_err(Error::from)
+}
+
 #[derive(serde::Serialize)]
 struct GroupInvite {
+    invite_id: i32,
     group_id: Uuid,
     group_name: String,
 }
@@ -91,35 +165,13 @@ enum InviteAction {
     Refuse,
 }
 
-#[derive(serde::Serialize, Debug)]
-struct CalendarEntry {
-    id: i32,
-    time: chrono::NaiveDate,
-    title: String,
-    description: Option<String>,
-}
-
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
-struct CalendarCreator {
-    time: chrono::NaiveDate, // .toISOString().substr(0, 10)
-    title: String,
-    description: Option<String>,
-}
-
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
-struct CalendarUpdator {
-    time: Option<chrono::NaiveDate>,
-    title: Option<String>,
-    description: Option<String>,
-}
-
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 struct IDResult {
     id: i32,
 }
 
 #[derive(serde::Serialize, Debug)]
-struct Paginate<T> {
+pub(super) struct Paginate<T> {
     taken: i64,
     total_count: i64,
     total_pages: i64,
@@ -127,18 +179,13 @@ struct Paginate<T> {
     content: Vec<T>,
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct GetCalendarEntries {
-    page: i64,
-    page_size: i64,
-}
-
 impl<T> Paginate<T> {
-    fn contain(content: Vec<T>, total_count: i64, page_size: i64, page_index: i64) -> Self {
+    pub(super) fn contain(content: Vec<T>, total_count: i64, page_size: i64, page_index: i64) -> Self {
         Self {
             taken: content.len() as i64,
             total_count,
-            total_pages: total_count / page_size,
+            // a partly filled last page is still a page
+            total_pages: (total_count + page_size - 1) / page_size,
             page_index,
             content,
         }
@@ -149,140 +196,124 @@ async fn get_calendar_entry(
     Path(entry_id): Path<i32>,
     State(pool): State<PgPool>,
     cookies: Cookies,
-) -> Result<JSON<CalendarEntry>, Error> {
+) -> Result<JSON<entries::Entry>, Error> {
     let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
-    sqlx::query_as!(
-        CalendarEntry,
-        "
-SELECT id, time, title, description
-FROM meet.user_calendar_entries
-WHERE owner_id=$1 AND id=$2
-",
-        owner_id,
-        entry_id
-    )
-    .fetch_one(&pool)
-    .await
-    .map(axum::Json)
-    .map_err(Error::from)
+    entries::fetch(&pool, Owner::Account(owner_id), entry_id)
+        .await
+        .map(axum::Json)
 }
 
 async fn make_calendar_entry(
     cookies: Cookies,
     State(pool): State<PgPool>,
-    Json(entry): JSON<CalendarCreator>,
-) -> Result<JSON<IDResult>, Error> {
+    Payload(submitted, entry): Payload<entries::Creator>,
+) -> Result<Response, Error> {
     let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
-    sqlx::query_as!(
-        IDResult,
-        "
-INSERT INTO meet.user_calendar_entries
-(time, title, description, owner_id)
-VALUES ($1, $2, $3, $4)
-RETURNING id",
-        entry.time,
-        entry.title,
-        entry.description,
-        owner_id
-    )
-    .fetch_one(&pool)
-    .await
-    .map(axum::Json)
-    .map_err(Error::from)
+    let zone = calendar::account_zone(&pool, owner_id).await?;
+    let created = entries::create(&pool, Owner::Account(owner_id), &zone, entry).await;
+    submitted.reply(created.map(|created| IDResult { id: created.id }))
 }
 
 async fn delete_calendar_entry(
     Path(entry_id): Path<i32>,
     State(pool): State<PgPool>,
     cookies: Cookies,
-) -> Result<JSON<CalendarEntry>, Error> {
+    submitted: Submitted,
+    Query(scope): Query<entries::EditScope>,
+) -> Result<Response, Error> {
     let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
-    sqlx::query_as!(
-        CalendarEntry,
-        "
-DELETE FROM meet.user_calendar_entries
-WHERE id=$1 AND owner_id=$2
-RETURNING id, time, title, description",
-        entry_id,
-        owner_id
-    )
-    .fetch_one(&pool)
-    .await
-    .map(axum::Json)
-    .map_err(Error::from)
+    let deleted = entries::delete(&pool, Owner::Account(owner_id), entry_id, scope).await;
+    submitted.reply(deleted)
 }
 
 async fn update_calendar_entry(
     Path(entry_id): Path<i32>,
     State(pool): State<PgPool>,
     cookies: Cookies,
-    Json(entry): JSON<CalendarUpdator>,
-) -> Result<JSON<CalendarEntry>, Error> {
+    Query(scope): Query<entries::EditScope>,
+    Payload(submitted, entry): Payload<entries::Updator>,
+) -> Result<Response, Error> {
     let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
-    sqlx::query_as!(
-        CalendarEntry,
-        "
-UPDATE meet.user_calendar_entries as e
-SET
-    time=COALESCE(e.time, $1),
-    title=COALESCE(e.title, $2),
-    description=COALESCE(e.description, $3)
-WHERE id=$4 AND owner_id=$5
-RETURNING id, time, title, description
-",
-        entry.time,
-        entry.title,
-        entry.description,
-        entry_id,
-        owner_id
-    )
-    .fetch_one(&pool)
-    .await
-    .map(axum::Json)
-    .map_err(Error::from)
+    let updated = entries::update(&pool, Owner::Account(owner_id), entry_id, scope, entry).await;
+    submitted.reply(updated)
 }
 
+#[derive(serde::Deserialize, Debug)]
+struct CalendarQuery {
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+    // comma separated ids of groups whose calendars are merged in
+    groups: Option<String>,
+}
+
+/// Personal entries and those of the chosen groups overlapping `from..to`
 async fn get_calendar_entries(
     State(pool): State<PgPool>,
     cookies: Cookies,
-    Query(calendar_query): Query<GetCalendarEntries>,
-) -> Result<JSON<Paginate<CalendarEntry>>, Error> {
+    Query(query): Query<CalendarQuery>,
+) -> Result<JSON<Vec<calendar::Occurrence>>, Error> {
     let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
-    let page_index = calendar_query.page;
-    let page_size = calendar_query.page_size;
-    let offset = page_index * page_size;
-    let entries_count = sqlx::query!(
-        "
-SELECT COUNT(*)
-FROM meet.user_calendar_entries
-WHERE owner_id=$1
-",
-        owner_id
-    )
-    .fetch_one(&pool)
-    .await?
-    .count
-    .unwrap_or(0);
-    let entries = sqlx::query_as!(
-        CalendarEntry,
-        "
-SELECT id, title, description, time
-FROM meet.user_calendar_entries
-WHERE owner_id=$1
-LIMIT $2 OFFSET $3
-",
-        owner_id,
-        page_size,
-        offset
-    )
-    .fetch_all(&pool)
-    .await?;
-    Ok(axum::Json(Paginate::contain(
-        entries,
-        entries_count,
-        page_size,
-        page_index,
-    )))
+    let range = calendar::Range { from: query.from, to: query.to }.check()?;
+    let mut groups = vec![];
+    for id in query.groups.iter().flat_map(|g| g.split(',')).filter(|g| !g.is_empty()) {
+        let id: Uuid = id
+            .trim()
+            .parse()
+            .or(Err(InvalidEntry(format!("invalid group id \"{id}\""))))?;
+        groups.push(id);
+    }
+    groups.sort();
+    groups.dedup();
+    for group_id in &groups {
+        group::authorize(&pool, *group_id, owner_id, Action::Read).await?;
+    }
+    calendar::in_range(&pool, Some(owner_id), &groups, range)
+        .await
+        .map(axum::Json)
+}
+
+async fn get_calendar_feed(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    host: Option<Host>,
+) -> Result<JSON<ical::FeedInfo>, Error> {
+    let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
+    let token = ical::token(&pool, Owner::Account(owner_id), false).await?;
+    Ok(axum::Json(ical::FeedInfo::new(host, &token)))
+}
+
+/// Replaces the feed's token, cutting off every existing subscription
+async fn regenerate_calendar_feed(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    host: Option<Host>,
+) -> Result<JSON<ical::FeedInfo>, Error> {
+    let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
+    let token = ical::token(&pool, Owner::Account(owner_id), true).await?;
+    Ok(axum::Json(ical::FeedInfo::new(host, &token)))
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ImportQuery {
+    // report what would be imported without storing it
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Imports the events of an uploaded or pasted `.ics` file
+async fn import_calendar(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    host: Option<Host>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<JSON<ical::ImportReport>, Error> {
+    let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
+    let zone = calendar::account_zone(&pool, owner_id).await?;
+    let domain = ical::domain(host.as_ref().map(|h| h.0.as_str()));
+    ical::import(&pool, Owner::Account(owner_id), &zone, &domain, &body, query.dry_run)
+        .await
+        .map(axum::Json)
 }
 
 #[derive(serde::Serialize, Debug)]
@@ -301,10 +332,27 @@ struct NoteUpdator {
     content: Option<String>,
 }
 
+const MAX_PAGE_SIZE: i64 = 100;
+
 #[derive(serde::Deserialize, Debug)]
-struct GetNoteEntries {
-    page: i64,
-    page_size: i64,
+pub(super) struct GetNoteEntries {
+    pub page: i64,
+    pub page_size: i64,
+}
+
+impl GetNoteEntries {
+    /// Rows to skip, after checking the page could exist
+    pub(super) fn offset(&self) -> Result<i64, Error> {
+        if !(1..=MAX_PAGE_SIZE).contains(&self.page_size) {
+            return Err(InvalidPage(format!("page_size must be between 1 and {MAX_PAGE_SIZE}")));
+        }
+        if self.page < 0 {
+            return Err(InvalidPage("page can't be negative".to_owned()));
+        }
+        self.page
+            .checked_mul(self.page_size)
+            .ok_or(InvalidPage("page out of range".to_owned()))
+    }
 }
 
 async fn make_note(
@@ -404,9 +452,9 @@ async fn get_notes(
     Query(notes_query): Query<GetNoteEntries>,
 ) -> Result<Json<Paginate<Note>>, Error> {
     let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
+    let offset = notes_query.offset()?;
     let page_index = notes_query.page;
     let page_size = notes_query.page_size;
-    let offset = page_index * page_size;
     let entries_count = sqlx::query!(
         "
 SELECT COUNT(*)
@@ -457,6 +505,7 @@ struct GroupUpdator {
 #[derive(serde::Serialize)]
 struct Group {
     id: Uuid,
+    owner_id: Uuid,
     name: String,
     description: Option<String>,
 }
@@ -475,7 +524,7 @@ WHERE user_id=$1"#, acc.id)
         .map(|s|s.group_id)
         .collect();
     sqlx::query_as!(Group, r#"
-SELECT g.id, name, description
+SELECT g.id, owner_id, name, description
 FROM meet.groups as g
 INNER JOIN meet.group_users as u ON g.id=u.group_id
 WHERE id IN (SELECT unnest($1::uuid[])) AND u.user_id=$2
@@ -493,7 +542,7 @@ async fn get_group(
 ) -> Result<JSON<Group>, Error> {
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
     sqlx::query_as!(Group, r#"
-SELECT id, name, description
+SELECT id, owner_id, name, description
 FROM meet.groups as g
 INNER JOIN meet.group_users as u ON g.id=u.group_id
 WHERE g.id=$1 AND u.user_id=$2
@@ -511,20 +560,36 @@ async fn update_group(
     Json(entry): JSON<GroupUpdator>,
 ) -> Result<JSON<Group>, Error> {
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
-    sqlx::query_as!(Group, r#"
+    group::authorize(&pool, group_id, acc.id, Action::Own).await?;
+    let mut tx = pool.begin().await?;
+    if let Some(owner_id) = entry.owner_id.filter(|id| *id != acc.id) {
+        // the previous owner stays on as an admin
+        let handed = sqlx::query!(r#"
+UPDATE meet.group_users SET
+  role=CASE WHEN user_id=$2 THEN 'owner' ELSE 'admin' END
+WHERE group_id=$1 AND user_id IN ($2, $3)
+"#, group_id, owner_id, acc.id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if handed != 2 {
+            Err(LogicError("Ownership can only go to a member".to_owned()))?;
+        }
+    }
+    let group = sqlx::query_as!(Group, r#"
 UPDATE meet.groups as g SET
-  owner_id=COALESCE(g.owner_id, $1),
-  name=COALESCE(g.name, $2),
-  description=COALESCE(g.description, $3)
-WHERE g.id=$4 AND g.owner_id=$5
+  owner_id=COALESCE($1, g.owner_id),
+  name=COALESCE($2, g.name),
+  description=COALESCE($3, g.description)
+WHERE g.id=$4
 RETURNING
-  id, name, description
+  id, owner_id, name, description
 "#, entry.owner_id, entry.name,
-entry.description, group_id, acc.id)
-        .fetch_one(&pool)
-        .await
-        .map(axum::Json)
-        .map_err(Error::from)
+entry.description, group_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(axum::Json(group))
 }
 
 async fn create_group(
@@ -539,15 +604,15 @@ INSERT INTO meet.groups
 VALUES
   ($1, $2, $3)
 RETURNING
-  id, name, description
+  id, owner_id, name, description
 "#, acc.id, entry.name, entry.description)
         .fetch_one(&pool)
         .await?;
     sqlx::query!(r#"
 INSERT INTO meet.group_users
-  (user_id, group_id)
+  (user_id, group_id, role)
 VALUES
-  ($1, $2)
+  ($1, $2, 'owner')
 "#, acc.id, group.id).execute(&pool).await?;
     Ok(axum::Json(group))
 }
@@ -558,44 +623,85 @@ async fn delete_group(
     Path(group_query): Path<Uuid>,
 ) -> Result<JSON<Group>, Error> {
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    group::authorize(&pool, group_query, acc.id, Action::Own).await?;
     sqlx::query_as!(Group, r#"
 DELETE FROM meet.groups as g
-WHERE g.id=$1 AND g.owner_id=$2
-RETURNING id, name, description
-"#, group_query, acc.id)
+WHERE g.id=$1
+RETURNING id, owner_id, name, description
+"#, group_query)
         .fetch_one(&pool)
         .await
         .map(axum::Json)
         .map_err(Error::from)
 }
 
-use accounts::Account;
+#[derive(serde::Serialize)]
+struct Member {
+    id: Uuid,
+    name: String,
+    role: Role,
+}
+
 async fn list_group_users(
     State(pool): State<PgPool>,
     Path(group_id): Path<Uuid>,
     cookies: Cookies,
-) -> Result<JSON<Vec<Account>>, Error> {
+) -> Result<JSON<Vec<Member>>, Error> {
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
-    let on_group = sqlx::query!(r#"
-SELECT COALESCE(COUNT(*), 0) as "count!" FROM meet.group_users as g
-WHERE g.group_id=$1 AND g.user_id=$2
-LIMIT 1
-"#, group_id, acc.id)
-        .fetch_one(&pool)
-        .await?
-        .count == 1;
-    if !on_group {
-        Err(AuthError("Only members can list group users".to_owned()))?;
-    }
-    sqlx::query_as!(Account, r#"
-SELECT id, name FROM inter.accounts as a
+    group::authorize(&pool, group_id, acc.id, Action::Read).await?;
+    let members = sqlx::query!(r#"
+SELECT id, name, g.role FROM inter.accounts as a
 INNER JOIN meet.group_users as g ON g.user_id=a.id
 WHERE g.group_id=$1
 "#, group_id)
         .fetch_all(&pool)
+        .await?
+        .into_iter()
+        .map(|m| Member { id: m.id, name: m.name, role: Role::from_db(&m.role) })
+        .collect();
+    Ok(axum::Json(members))
+}
+
+async fn get_group_feed(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    host: Option<Host>,
+) -> Result<JSON<ical::FeedInfo>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    group::authorize(&pool, group_id, acc.id, Action::Read).await?;
+    let token = ical::token(&pool, Owner::Group(group_id), false).await?;
+    Ok(axum::Json(ical::FeedInfo::new(host, &token)))
+}
+
+async fn regenerate_group_feed(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    host: Option<Host>,
+) -> Result<JSON<ical::FeedInfo>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    group::authorize(&pool, group_id, acc.id, Action::Own).await?;
+    let token = ical::token(&pool, Owner::Group(group_id), true).await?;
+    Ok(axum::Json(ical::FeedInfo::new(host, &token)))
+}
+
+async fn import_group_calendar(
+    State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
+    cookies: Cookies,
+    host: Option<Host>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<JSON<ical::ImportReport>, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    group::authorize(&pool, group_id, acc.id, Action::Write).await?;
+    // floating times are read in the importing member's zone
+    let zone = calendar::account_zone(&pool, acc.id).await?;
+    let domain = ical::domain(host.as_ref().map(|h| h.0.as_str()));
+    ical::import(&pool, Owner::Group(group_id), &zone, &domain, &body, query.dry_run)
         .await
         .map(axum::Json)
-        .map_err(Error::from)
 }
 
 async fn invite_to_group(
@@ -604,46 +710,69 @@ async fn invite_to_group(
     cookies: Cookies,
 ) -> Result<StatusCode, Error> {
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
-    let is_group_owner = sqlx::query!(r#"
-SELECT owner_id FROM meet.groups
-WHERE id=$1
-"#, group_id)
-        .fetch_one(&pool)
-        .await?
-        .owner_id == acc.id;
-    if !is_group_owner {
-        Err(AuthError("Only owners can invite users to group".to_owned()))?;
-    }
+    group::authorize(&pool, group_id, acc.id, Action::Manage).await?;
+    store_invite(&pool, group_id, user_id).await
+}
+
+/// Callers check that the inviting account may invite to the group
+async fn store_invite(pool: &PgPool, group_id: Uuid, user_id: Uuid) -> Result<StatusCode, Error> {
     sqlx::query!(r#"
 INSERT into meet.group_invites
   (user_id, group_id)
 VALUES
   ($1, $2)
 "#, user_id, group_id)
-        .execute(&pool)
+        .execute(pool)
         .await?;
     Ok(StatusCode::CREATED)
 }
 
-async fn remove_user(
+#[derive(serde::Deserialize)]
+struct InviteByName {
+    name: String,
+}
+
+async fn invite_by_name(
     State(pool): State<PgPool>,
+    Path(group_id): Path<Uuid>,
     cookies: Cookies,
-    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+    Json(invite): JSON<InviteByName>,
 ) -> Result<StatusCode, Error> {
+    // before the lookups, so outsiders can't probe for accounts and members
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
-    let is_group_owner = sqlx::query!(r#"
-SELECT owner_id FROM meet.groups
-WHERE id=$1
-"#, group_id)
+    group::authorize(&pool, group_id, acc.id, Action::Manage).await?;
+    let name = invite.name.trim();
+    let user_id = sqlx::query!("SELECT id FROM inter.accounts WHERE name=$1", name)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or_else(|| UnknownUser(name.to_owned()))?
+        .id;
+    let on_group = sqlx::query!(r#"
+SELECT COALESCE(COUNT(*), 0) as "count!" FROM meet.group_users as g
+WHERE g.group_id=$1 AND g.user_id=$2
+"#, group_id, user_id)
         .fetch_one(&pool)
         .await?
-        .owner_id == acc.id;
-    if !is_group_owner {
-        Err(AuthError("Only owners can remove users from group".to_owned()))?;
+        .count == 1;
+    if on_group {
+        Err(LogicError(format!("{name} is already a member")))?;
     }
+    store_invite(&pool, group_id, user_id).await
+}
+
+async fn remove_user(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, Error> {
+    let acc = crate::accounts::get_acc(&cookies, &pool).await?;
+    let role = group::authorize(&pool, group_id, acc.id, Action::Manage).await?;
     if acc.id == user_id {
         Err(LogicError("Can't remove self from group".to_owned()))?;
     }
+    if group::authorize(&pool, group_id, user_id, Action::Read).await? >= role {
+        Err(AuthError("Only members below one's own role can be removed".to_owned()))?;
+    }
     sqlx::query!(r#"
 DELETE FROM meet.group_users
 WHERE user_id=$1 AND group_id=$2
@@ -657,7 +786,7 @@ async fn list_invites(
 ) -> Result<JSON<Vec<GroupInvite>>, Error> {
     let acc = crate::accounts::get_acc(&cookies, &pool).await?;
     sqlx::query_as!(GroupInvite, r#"
-SELECT g.id as "group_id", g.name as "group_name"
+SELECT i.invite_id, g.id as "group_id", g.name as "group_name"
 FROM meet.groups as g
 INNER JOIN meet.group_invites as i ON i.group_id=g.id
 WHERE i.user_id=$1
@@ -694,7 +823,7 @@ VALUES
         .execute(&pool)
         .await?;
     sqlx::query_as!(Group, r#"
-SELECT id, name, description
+SELECT id, owner_id, name, description
 FROM meet.groups as g
 WHERE g.id=$1
 "#, group_id)
@@ -705,22 +834,40 @@ WHERE g.id=$1
         .map_err(Error::from)
 }
 
+/// The calendar alone, for htmx to swap into the page
+async fn calendar_view(
+    State(pool): State<PgPool>,
+    cookies: Cookies,
+    Query(query): Query<views::ViewQuery>,
+) -> Result<Markup, Error> {
+    let owner_id = crate::accounts::get_acc(&cookies, &pool).await?.id;
+    views::render(&pool, owner_id, &query).await
+}
+
 async fn index(
     State(pool): State<PgPool>,
     cookies: Cookies,
+    Query(query): Query<views::ViewQuery>,
 ) -> Result<Markup, axum::response::Redirect> {
+    let calendar = match crate::accounts::get_acc(&cookies, &pool).await {
+        Ok(acc) => views::render(&pool, acc.id, &query)
+            .await
+            .unwrap_or_else(|e| html! { div id="calendar" { (e.message()) } }),
+        Err(_) => html! { div id="calendar" { "Log in to see your calendar." } },
+    };
     Ok(html!{
         (DOCTYPE)
         head {
             (CSS("/files/style.css"));
             (CSS("/files/css/meet.css"));
+            (HTMX);
             (JS("/files/js/meet.js"));
         }
         body {
             (nav("/meet/user", &cookies, &pool).await);
             div id="container" {
                 div id="groups" { "groups" }
-                div id="calendar" { "calendar" }
+                (calendar)
                 div id="notes" {
                     div id="note-creator-container" {}
                     div id="note-container" {}
@@ -729,4 +876,3 @@ async fn index(
         }
     })
 }
-
@@ -0,0 +1,485 @@
+//! Server-rendered month, week and agenda views of the calendar.
+//!
+//! Navigation is plain links that htmx upgrades into swaps of
+//! `#calendar`, and entries are edited in `<details>` forms posting to the
+//! regular calendar endpoints, so the page works without any script.
+//! Those endpoints redirect plain form posts back to the page and answer
+//! htmx with a `calendar-changed` event the view reloads itself on.
+//! Failures end up in `#calendar-error`, through an `error` parameter of
+//! the redirect or swapped in by htmx.
+use super::calendar::{self, Occurrence, Range};
+use super::user::Error;
+use crate::*;
+use axum::{
+    extract::{Form, FromRequest, FromRequestParts, Json, Request},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::{
+    DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, SecondsFormat, TimeDelta, Utc,
+};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+const CHANGED: &str = "calendar-changed";
+const ERROR_SLOT: &str = "#calendar-error";
+const AGENDA_DAYS: u64 = 30;
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+// dates asked for are clamped to these, `period` reaches a few weeks past them
+const FIRST_DATE: NaiveDate = NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+const LAST_DATE: NaiveDate = NaiveDate::from_ymd_opt(9999, 12, 31).unwrap();
+
+/// How a change reached the server, which decides how it is answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Submitted {
+    Json,
+    Htmx,
+    // a plain form post, redirected back to the page it came from
+    Form(String),
+}
+
+impl Submitted {
+    fn of(headers: &HeaderMap) -> Self {
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if headers.contains_key("HX-Request") {
+            Submitted::Htmx
+        } else if content_type.starts_with("application/x-www-form-urlencoded") {
+            Submitted::Form(back(headers))
+        } else {
+            Submitted::Json
+        }
+    }
+
+    /// Failures of JSON requests stay errors, forms show them on the page
+    pub fn reply<T: serde::Serialize>(self, result: Result<T, Error>) -> Result<Response, Error> {
+        let value = match result {
+            Ok(value) => value,
+            Err(e) if self == Submitted::Json => return Err(e),
+            Err(e) => return Ok(self.fail(&e.to_string())),
+        };
+        Ok(match self {
+            Submitted::Json => Json(value).into_response(),
+            Submitted::Htmx => ([("HX-Trigger", CHANGED)], "").into_response(),
+            Submitted::Form(back) => Redirect::to(&back).into_response(),
+        })
+    }
+
+    fn fail(self, message: &str) -> Response {
+        match self {
+            Submitted::Json => (StatusCode::UNPROCESSABLE_ENTITY, message.to_owned()).into_response(),
+            // htmx swaps nothing of an error status, so this one is a 200
+            Submitted::Htmx => (
+                [("HX-Retarget", ERROR_SLOT), ("HX-Reswap", "innerHTML")],
+                html! {(message)},
+            )
+                .into_response(),
+            Submitted::Form(back) => {
+                let separator = if back.contains('?') { '&' } else { '?' };
+                let back = format!("{back}{separator}error={}", urlencoding::encode(message));
+                Redirect::to(&back).into_response()
+            }
+        }
+    }
+}
+
+/// The meet page the form was on, so its view and date survive the post,
+/// without the error of an earlier one
+fn back(headers: &HeaderMap) -> String {
+    let referer = headers
+        .get(header::REFERER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let path = referer
+        .split_once("://")
+        .and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]))
+        .unwrap_or("/meet/user");
+    if !path.starts_with("/meet/user") {
+        return "/meet/user".to_owned();
+    }
+    match path.split_once('?') {
+        Some((page, query)) => {
+            let kept: Vec<_> = query.split('&').filter(|p| !p.starts_with("error=")).collect();
+            match kept.is_empty() {
+                true => page.to_owned(),
+                false => format!("{page}?{}", kept.join("&")),
+            }
+        }
+        None => path.to_owned(),
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Submitted {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Submitted::of(&parts.headers))
+    }
+}
+
+/// A body sent either as JSON or as a form
+pub struct Payload<T>(pub Submitted, pub T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for Payload<T>
+where
+    T: serde::de::DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let submitted = Submitted::of(req.headers());
+        let value = match submitted {
+            Submitted::Json => Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?
+                .0,
+            _ => Form::<T>::from_request(req, state)
+                .await
+                .map_err(|e| submitted.clone().fail(&e.body_text()))?
+                .0,
+        };
+        Ok(Payload(submitted, value))
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    #[default]
+    Month,
+    Week,
+    Agenda,
+}
+
+impl View {
+    fn as_str(self) -> &'static str {
+        match self {
+            View::Month => "month",
+            View::Week => "week",
+            View::Agenda => "agenda",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct ViewQuery {
+    #[serde(default)]
+    view: View,
+    // today when missing
+    date: Option<NaiveDate>,
+    // why the last form post failed
+    error: Option<String>,
+}
+
+/// Days shown, the end exclusive, and the dates the arrows lead to
+struct Period {
+    first: NaiveDate,
+    end: NaiveDate,
+    previous: NaiveDate,
+    next: NaiveDate,
+    title: String,
+}
+
+fn monday(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday().into())
+}
+
+fn period(view: View, date: NaiveDate) -> Period {
+    match view {
+        View::Month => {
+            let start = date.with_day(1).unwrap_or(date);
+            let after = start + Months::new(1);
+            Period {
+                first: monday(start),
+                end: monday(after - Days::new(1)) + Days::new(7),
+                previous: start - Months::new(1),
+                next: after,
+                title: start.format("%B %Y").to_string(),
+            }
+        }
+        View::Week => {
+            let first = monday(date);
+            Period {
+                first,
+                end: first + Days::new(7),
+                previous: first - Days::new(7),
+                next: first + Days::new(7),
+                title: format!("Week of {}", first.format("%-d %B %Y")),
+            }
+        }
+        View::Agenda => Period {
+            first: date,
+            end: date + Days::new(AGENDA_DAYS),
+            previous: date - Days::new(AGENDA_DAYS),
+            next: date + Days::new(AGENDA_DAYS),
+            title: format!("From {}", date.format("%-d %B %Y")),
+        },
+    }
+}
+
+// where a DST change skips midnight the day starts an hour later
+fn midnight(zone: Tz, date: NaiveDate) -> Result<DateTime<Utc>, Error> {
+    let time = date.and_time(NaiveTime::MIN);
+    calendar::to_utc(zone, time).or_else(|_| calendar::to_utc(zone, time + TimeDelta::hours(1)))
+}
+
+/// Local days an occurrence covers, all-day ones in the zone they were planned in
+fn days(zone: Tz, occurrence: &Occurrence) -> (NaiveDate, NaiveDate) {
+    if occurrence.all_day {
+        let own = calendar::parse_zone(&occurrence.time_zone).unwrap_or(zone);
+        let first = calendar::to_local(own, occurrence.starts_at).date();
+        let end = calendar::to_local(own, occurrence.ends_at).date();
+        (first, end.pred_opt().unwrap_or(first).max(first))
+    } else {
+        let first = calendar::to_local(zone, occurrence.starts_at).date();
+        let last = calendar::to_local(zone, occurrence.ends_at - TimeDelta::seconds(1)).date();
+        (first, last.max(first))
+    }
+}
+
+fn page_url(view: View, date: NaiveDate) -> String {
+    format!("/meet/user?view={}&date={date}", view.as_str())
+}
+
+fn fragment_url(view: View, date: NaiveDate) -> String {
+    format!("/meet/user/calendar/view?view={}&date={date}", view.as_str())
+}
+
+fn link(view: View, date: NaiveDate, class: &str, text: &str) -> Markup {
+    html! {
+        a class=(class) href=(page_url(view, date))
+            hx-get=(fragment_url(view, date)) hx-target="#calendar"
+            hx-swap="outerHTML" hx-push-url=(page_url(view, date))
+            {(text)}
+    }
+}
+
+// value of a datetime-local input, all-day ends shown as their last day
+fn input_value(occurrence: &Occurrence, time: DateTime<Utc>, end: bool) -> String {
+    let zone = calendar::parse_zone(&occurrence.time_zone).unwrap_or(Tz::UTC);
+    let mut local = calendar::to_local(zone, time);
+    if occurrence.all_day && end {
+        local -= TimeDelta::days(1);
+    }
+    local.format("%Y-%m-%dT%H:%M").to_string()
+}
+
+fn all_day_select(all_day: bool) -> Markup {
+    html! {
+        select name="all_day" {
+            option value="false" selected[!all_day] {"timed"}
+            option value="true" selected[all_day] {"all day"}
+        }
+    }
+}
+
+fn entry_form(occurrence: &Occurrence) -> Markup {
+    let base = format!("/meet/user/calendar/{}", occurrence.id);
+    let scoped = |path: &str, scope: &str| match occurrence.occurrence {
+        Some(at) => format!(
+            "{path}?scope={scope}&occurrence={}",
+            urlencoding::encode(&at.to_rfc3339_opts(SecondsFormat::Secs, true))
+        ),
+        None => path.to_owned(),
+    };
+    let delete = format!("{base}/delete");
+    html! {
+        form.calendar-entry-form method="POST" action=(base) hx-post=(base) hx-swap="none" {
+            input type="text" name="title" value=(occurrence.title) required {}
+            input type="datetime-local" name="starts_at"
+                value=(input_value(occurrence, occurrence.starts_at, false)) {}
+            input type="datetime-local" name="ends_at"
+                value=(input_value(occurrence, occurrence.ends_at, true)) {}
+            (all_day_select(occurrence.all_day))
+            input type="text" name="time_zone" value=(occurrence.time_zone) {}
+            input type="text" name="rrule" placeholder="recurrence, e.g. FREQ=WEEKLY"
+                value=(occurrence.rrule.clone().unwrap_or_default()) {}
+            textarea name="description" {(occurrence.description.clone().unwrap_or_default())}
+            @if occurrence.occurrence.is_some() {
+                @for (scope, label) in [("this", "save this one"), ("following", "save this and following"), ("all", "save all")] {
+                    @let url = scoped(&base, scope);
+                    button formaction=(url) hx-post=(url) {(label)}
+                }
+            } @else {
+                button {"save"}
+            }
+        }
+        form.calendar-entry-delete method="POST" action=(delete) hx-post=(delete) hx-swap="none" {
+            @if occurrence.occurrence.is_some() {
+                @for (scope, label) in [("this", "delete this one"), ("following", "delete this and following"), ("all", "delete all")] {
+                    @let url = scoped(&delete, scope);
+                    button formaction=(url) hx-post=(url) {(label)}
+                }
+            } @else {
+                button {"delete"}
+            }
+        }
+    }
+}
+
+fn entry(zone: Tz, occurrence: &Occurrence, groups: &BTreeMap<Uuid, String>) -> Markup {
+    let time = match occurrence.all_day {
+        true => "all day".to_owned(),
+        false => calendar::to_local(zone, occurrence.starts_at).format("%H:%M").to_string(),
+    };
+    html! {
+        details.calendar-entry.group-entry[occurrence.group_id.is_some()] {
+            summary {
+                span.entry-time {(time)} " " (occurrence.title)
+                @if occurrence.rrule.is_some() { " ↻" }
+            }
+            @match occurrence.group_id {
+                Some(group_id) => {
+                    p.entry-group {(groups.get(&group_id).map(String::as_str).unwrap_or("group"))}
+                    @if let Some(description) = &occurrence.description {
+                        p {(description)}
+                    }
+                }
+                None => (entry_form(occurrence)),
+            }
+        }
+    }
+}
+
+fn create_form(date: NaiveDate, time_zone: &str) -> Markup {
+    html! {
+        details.calendar-new {
+            summary {"new entry"}
+            form method="POST" action="/meet/user/calendar" hx-post="/meet/user/calendar" hx-swap="none" {
+                input type="text" name="title" placeholder="title" required {}
+                input type="datetime-local" name="starts_at" value={(date) "T09:00"} required {}
+                input type="datetime-local" name="ends_at" {}
+                (all_day_select(false))
+                input type="text" name="time_zone" value=(time_zone) {}
+                input type="text" name="rrule" placeholder="recurrence, e.g. FREQ=WEEKLY" {}
+                textarea name="description" placeholder="description" {}
+                button {"create"}
+            }
+        }
+    }
+}
+
+fn day_entries(
+    zone: Tz,
+    date: NaiveDate,
+    by_day: &BTreeMap<NaiveDate, Vec<&Occurrence>>,
+    groups: &BTreeMap<Uuid, String>,
+) -> Markup {
+    html! {
+        @for occurrence in by_day.get(&date).into_iter().flatten() {
+            (entry(zone, occurrence, groups))
+        }
+    }
+}
+
+/// `#calendar` for `account`, also swapped in on its own by htmx
+pub async fn render(pool: &PgPool, account: Uuid, query: &ViewQuery) -> Result<Markup, Error> {
+    let time_zone = calendar::account_zone(pool, account).await?;
+    let zone = calendar::parse_zone(&time_zone)?;
+    let today = calendar::to_local(zone, Utc::now()).date();
+    let date = query.date.unwrap_or(today).clamp(FIRST_DATE, LAST_DATE);
+    let view = query.view;
+    let period = period(view, date);
+    let range = Range {
+        from: midnight(zone, period.first)?,
+        to: midnight(zone, period.end)?,
+    };
+    let groups: BTreeMap<Uuid, String> = sqlx::query!("
+SELECT g.id, g.name
+FROM meet.groups as g
+INNER JOIN meet.group_users as u ON u.group_id=g.id
+WHERE u.user_id=$1
+", account)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|g| (g.id, g.name))
+        .collect();
+    let group_ids: Vec<Uuid> = groups.keys().copied().collect();
+    let mut occurrences = calendar::in_range(pool, Some(account), &group_ids, range).await?;
+    // all-day entries head their days
+    occurrences.sort_by_key(|o| !o.all_day);
+    let mut by_day: BTreeMap<NaiveDate, Vec<&Occurrence>> = BTreeMap::new();
+    for occurrence in &occurrences {
+        let (first, last) = days(zone, occurrence);
+        let days = first.max(period.first).iter_days().take_while(|d| *d <= last && *d < period.end);
+        for day in days {
+            by_day.entry(day).or_default().push(occurrence);
+        }
+    }
+    let weeks: Vec<Vec<NaiveDate>> = period
+        .first
+        .iter_days()
+        .take_while(|d| *d < period.end)
+        .collect::<Vec<_>>()
+        .chunks(7)
+        .map(<[NaiveDate]>::to_vec)
+        .collect();
+    Ok(html! {
+        div #calendar hx-get=(fragment_url(view, date)) hx-trigger={(CHANGED) " from:body"} hx-swap="outerHTML" {
+            div.calendar-nav {
+                (link(view, period.previous, "calendar-previous", "‹"))
+                (link(view, today, "calendar-today", "today"))
+                (link(view, period.next, "calendar-next", "›"))
+                span.calendar-title {(period.title)}
+                span.calendar-views {
+                    @for other in [View::Month, View::Week, View::Agenda] {
+                        @let class = if other == view { "calendar-view current" } else { "calendar-view" };
+                        (link(other, date, class, other.as_str()))
+                    }
+                }
+            }
+            p #calendar-error role="alert" {(query.error.as_deref().unwrap_or_default())}
+            (create_form(date, &time_zone))
+            @match view {
+                View::Month => {
+                    table.calendar-month {
+                        tr { @for day in WEEKDAYS { th {(day)} } }
+                        @for week in &weeks {
+                            tr {
+                                @for day in week {
+                                    td.day.other-month[day.month() != date.month()].today[*day == today] {
+                                        div.day-number {(day.day())}
+                                        (day_entries(zone, *day, &by_day, &groups))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                View::Week => {
+                    table.calendar-week {
+                        @for week in &weeks {
+                            tr {
+                                @for day in week {
+                                    th.today[*day == today] {(day.format("%a %-d"))}
+                                }
+                            }
+                            tr {
+                                @for day in week {
+                                    td.day.today[*day == today] {(day_entries(zone, *day, &by_day, &groups))}
+                                }
+                            }
+                        }
+                    }
+                }
+                View::Agenda => {
+                    div.calendar-agenda {
+                        @if by_day.is_empty() {
+                            p {"Nothing planned."}
+                        }
+                        @for day in by_day.keys() {
+                            h3.today[*day == today] {(day.format("%A, %-d %B %Y"))}
+                            (day_entries(zone, *day, &by_day, &groups))
+                        }
+                    }
+                }
+            }
+        }
+    })
+}